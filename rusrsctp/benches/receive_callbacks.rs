@@ -0,0 +1,76 @@
+// Throughput of callback mode sockets which copy received data out of usrsctp's
+// buffer (socket_with_callbacks) against ones which keep the buffer itself
+// (socket_with_buffer_callbacks).  Run with `cargo bench`.
+
+extern crate rusrsctp;
+
+use std::net::Ipv4Addr;
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+use rusrsctp::*;
+
+const MESSAGES: usize = 20000;
+const MESSAGE_SIZE: usize = 16384;
+
+// Send MESSAGES messages to `server` and wait for all of their bytes to be
+// received (a message may be received in parts)
+fn run<D: AsRef<[u8]>>(sctp: &UsrSctp, mut server: Socket<Ipv4>, port: u16, rx: Receiver<D>)
+                       -> Duration
+{
+    server.bind(Ipv4Addr::new(127, 0, 0, 1), port).unwrap();
+    server.listen(8).unwrap();
+    let mut client = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    client.connect(Ipv4Addr::new(127, 0, 0, 1), port).unwrap();
+    let (_addr, _port, _conn) = server.accept().unwrap();
+    let data = vec![0x5a; MESSAGE_SIZE];
+    let start = Instant::now();
+    let mut kept = Vec::with_capacity(MESSAGES);
+    let mut received = 0;
+    for _ in 0..MESSAGES {
+        client.sendv(&data, None, None, None, None, MsgFlags::empty()).unwrap();
+        // keep what was received so far, as an application holding on to it would
+        while let Ok(part) = rx.try_recv() {
+            received += part.as_ref().len();
+            kept.push(part);
+        }
+    }
+    while received < MESSAGES * MESSAGE_SIZE {
+        let part = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        received += part.as_ref().len();
+        kept.push(part);
+    }
+    start.elapsed()
+}
+
+fn report(name: &str, elapsed: Duration) {
+    let bytes = (MESSAGES * MESSAGE_SIZE) as f64;
+    println!("{:>10}: {:8.1} MB/s ({} messages of {} bytes in {:?})", name,
+             bytes / elapsed.as_secs_f64() / 1_000_000.0, MESSAGES, MESSAGE_SIZE, elapsed);
+}
+
+fn main() {
+    let sctp = UsrSctp::new(Some(9899), false);
+
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let server = sctp.socket_with_callbacks::<Ipv4, OneToOne, _>(
+        move |data, _from, _rcv_info, flags| {
+            if !flags.contains(MsgFlags::NOTIFICATION) && !data.is_empty() {
+                let _ = tx.lock().unwrap().send(data.to_vec());
+            }
+        },
+        None).unwrap();
+    report("copying", run(&sctp, server, 5030, rx));
+
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let server = sctp.socket_with_buffer_callbacks::<Ipv4, OneToOne, _>(
+        move |data, _from, _rcv_info, flags| {
+            if !flags.contains(MsgFlags::NOTIFICATION) && !data.is_empty() {
+                let _ = tx.lock().unwrap().send(data);
+            }
+        },
+        None).unwrap();
+    report("zero-copy", run(&sctp, server, 5031, rx));
+}
@@ -0,0 +1,49 @@
+
+use std::mem;
+use errno::Errno;
+use rusrsctp_sys::*;
+use super::{Socket, Ip, Mode, AssocId, AsconfSupported, AutoAsconf, SetPeerPrimaryAddr};
+
+/// Dynamic address reconfiguration (ASCONF, RFC 5061).
+///
+/// With ASCONF, local addresses can be added and removed on live associations
+/// with `bindx`, and the peer can be asked to switch its primary destination,
+/// all without tearing the associations down.  Since ASCONF chunks must be
+/// authenticated, SCTP-AUTH must remain enabled.  Subscribe to
+/// `EventType::PeerAddrChange` to follow the resulting address changes.
+impl<'a, T: 'a + Ip, M: Mode> Socket<'a, T, M> {
+    /// Enable or disable ASCONF for associations created afterwards
+    pub fn set_asconf_supported(&mut self, on: bool) -> Result<(), Errno> {
+        self.setsockopt(AsconfSupported(sctp_assoc_value {
+            assoc_id: SCTP_FUTURE_ASSOC,
+            assoc_value: if on { 1 } else { 0 },
+        }))
+    }
+
+    /// Whether ASCONF is in use on an association (i.e. both sides support it).
+    /// Use SCTP_FUTURE_ASSOC to check whether we offer it.
+    pub fn get_asconf_supported(&mut self, assoc_id: AssocId) -> Result<bool, Errno> {
+        let opt = self.getsockopt(AsconfSupported(sctp_assoc_value {
+            assoc_id: assoc_id,
+            assoc_value: 0,
+        }))?;
+        Ok(opt.0.assoc_value != 0)
+    }
+
+    /// For sockets bound to the wildcard address: whether addresses appearing on
+    /// or disappearing from the host are automatically announced to peers.
+    pub fn set_auto_asconf(&mut self, on: bool) -> Result<(), Errno> {
+        self.setsockopt(AutoAsconf(if on { 1 } else { 0 }))
+    }
+
+    /// Ask the peer of an association to use `addr` (one of our addresses) as
+    /// its primary destination address.
+    pub fn set_peer_primary(&mut self, assoc_id: AssocId, addr: T::Addr, port: u16)
+                            -> Result<(), Errno>
+    {
+        let mut prim: sctp_setpeerprim = unsafe { mem::zeroed() };
+        prim.sspp_addr = T::to_sockaddr_storage(addr, port);
+        prim.sspp_assoc_id = assoc_id;
+        self.setsockopt(SetPeerPrimaryAddr(prim))
+    }
+}
@@ -0,0 +1,140 @@
+
+use std::collections::HashMap;
+use errno::Errno;
+use libc;
+use super::{AssocId, Message, MsgFlags, RcvInfo, SctpFlags, Notification, PdIndication};
+
+// Identifies a message while it is being received: association, stream, whether
+// it is unordered (ordered and unordered messages are numbered separately), and
+// ssn (the lower 16 bits of the MID with I-DATA)
+type MessageKey = (AssocId, u16, bool, u16);
+
+struct Partial {
+    data: Vec<u8>,
+    rcv_info: Option<RcvInfo>,
+    // Set once the message grew beyond the size cap; its remaining parts are
+    // then dropped as they arrive
+    oversized: bool,
+}
+
+/// Gathers the parts returned by `recvv` into complete messages.
+///
+/// A message larger than the partial delivery point is received in several
+/// parts, only the last of which carries `MsgFlags::EOR`.  With
+/// `FragmentInterleave` set to 2 (see `Socket::enable_interleaving`), the parts
+/// of messages on different streams or associations may arrive mixed together;
+/// the assembler tells them apart by their receive info, so the `RecvRcvInfo`
+/// option must be on.
+///
+/// Feed every `recvv` result (notifications included) to `push`.  When the
+/// partial delivery of a message is aborted, the parts gathered so far are
+/// discarded.
+pub struct MessageAssembler {
+    max_size: usize,
+    partial: HashMap<MessageKey, Partial>,
+}
+
+impl MessageAssembler {
+    /// Create an assembler which refuses messages larger than `max_size` bytes
+    pub fn new(max_size: usize) -> MessageAssembler {
+        MessageAssembler {
+            max_size: max_size,
+            partial: HashMap::new(),
+        }
+    }
+
+    /// Add the result of one `recvv`.  Returns the message if it is now complete.
+    /// Notifications are returned as they are (after handling any partial delivery
+    /// abort).  Returns `EMSGSIZE` once, when a message grows beyond the size
+    /// cap; the rest of that message is then dropped.  Returns `EINVAL` for data
+    /// without receive info, whose message can not be told.
+    pub fn push(&mut self, data: &[u8], rcv_info: Option<RcvInfo>, flags: MsgFlags)
+                -> Result<Option<Message>, Errno>
+    {
+        if flags.contains(MsgFlags::NOTIFICATION) {
+            if let Some(Notification::PartialDelivery(ev)) = Notification::parse(data) {
+                if ev.indication == PdIndication::Aborted {
+                    // The event does not say whether the message was unordered
+                    self.partial.retain(|key, _| {
+                        !(key.0 == ev.assoc_id && key.1 == ev.sid && key.3 == ev.seq as u16)
+                    });
+                }
+            }
+            return Ok(Some(Message {
+                data: data.to_vec(),
+                sid: 0,
+                ppid: 0,
+                flags: flags,
+                rcv_info: rcv_info,
+            }));
+        }
+
+        let key = match rcv_info {
+            Some(r) => (r.assoc_id, r.sid, r.flags.contains(SctpFlags::UNORDERED), r.ssn),
+            None => return Err(Errno(libc::EINVAL)),
+        };
+        let complete = flags.contains(MsgFlags::EOR);
+
+        // The common case: a whole message received at once
+        if complete && !self.partial.contains_key(&key) {
+            if data.len() > self.max_size {
+                return Err(Errno(libc::EMSGSIZE));
+            }
+            return Ok(Some(Message {
+                data: data.to_vec(),
+                sid: rcv_info.map(|r| r.sid).unwrap_or(0),
+                ppid: rcv_info.map(|r| r.ppid).unwrap_or(0),
+                flags: flags,
+                rcv_info: rcv_info,
+            }));
+        }
+
+        let max_size = self.max_size;
+        let mut too_big = false;
+        {
+            let partial = self.partial.entry(key).or_insert_with(|| Partial {
+                data: Vec::new(),
+                rcv_info: rcv_info,
+                oversized: false,
+            });
+            if !partial.oversized {
+                if partial.data.len() + data.len() > max_size {
+                    partial.oversized = true;
+                    partial.data = Vec::new();
+                    too_big = true;
+                } else {
+                    partial.data.extend_from_slice(data);
+                }
+            }
+        }
+        if !complete {
+            return if too_big { Err(Errno(libc::EMSGSIZE)) } else { Ok(None) };
+        }
+        let partial = self.partial.remove(&key).unwrap();
+        if too_big {
+            Err(Errno(libc::EMSGSIZE))
+        } else if partial.oversized {
+            Ok(None)
+        } else {
+            // the first part's receive info describes the message, except for the flags
+            let rcv_info = partial.rcv_info.or(rcv_info);
+            Ok(Some(Message {
+                data: partial.data,
+                sid: rcv_info.map(|r| r.sid).unwrap_or(0),
+                ppid: rcv_info.map(|r| r.ppid).unwrap_or(0),
+                flags: flags,
+                rcv_info: rcv_info,
+            }))
+        }
+    }
+
+    /// Discard the incomplete messages of an association, e.g. once it is gone
+    pub fn discard_assoc(&mut self, assoc_id: AssocId) {
+        self.partial.retain(|key, _| key.0 != assoc_id);
+    }
+
+    /// The number of messages which are incomplete
+    pub fn incomplete(&self) -> usize {
+        self.partial.len()
+    }
+}
@@ -0,0 +1,302 @@
+
+use std::future::Future;
+use std::io;
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use errno::Errno;
+use libc;
+use rusrsctp_sys::*;
+use tokio::io::Interest;
+use tokio::io::unix::AsyncFd;
+use super::{UsrSctp, Socket, Ip, OneToOne, Readiness, SndInfo, RcvInfo, MsgFlags, EventType,
+            Notification, AssocChangeState};
+
+fn would_block(e: Errno) -> bool {
+    let e = e.0 as u32;
+    e == EWOULDBLOCK || e == EAGAIN || e == EINPROGRESS || e == EALREADY
+}
+
+fn io_to_errno(e: io::Error) -> Errno {
+    Errno(e.raw_os_error().unwrap_or(libc::EIO))
+}
+
+// A non-blocking socket whose readiness bridge is registered with the tokio
+// reactor, so that usrsctp upcalls wake the tasks waiting on it.
+struct AsyncSocket<'a, T: 'a + Ip> {
+    socket: Socket<'a, T>,
+    readiness: AsyncFd<Readiness>,
+}
+
+impl<'a, T: 'a + Ip> AsyncSocket<'a, T> {
+    fn new(mut socket: Socket<'a, T>) -> Result<AsyncSocket<'a, T>, Errno> {
+        socket.set_non_blocking(true)?;
+        let readiness = match socket.readiness() {
+            Some(readiness) => readiness.clone(),
+            None => Readiness::new()?,
+        };
+        socket.set_readiness(&readiness)?;
+        let readiness = AsyncFd::with_interest(readiness, Interest::READABLE)
+            .map_err(io_to_errno)?;
+        Ok(AsyncSocket {
+            socket: socket,
+            readiness: readiness,
+        })
+    }
+
+    // Try `op` until it no longer would block, waiting for an upcall in between
+    fn poll_op<R, F>(&mut self, cx: &mut Context, mut op: F) -> Poll<Result<R, Errno>>
+        where F: FnMut(&mut Socket<'a, T>) -> Result<R, Errno>
+    {
+        loop {
+            let mut guard = match self.readiness.poll_read_ready(cx) {
+                Poll::Ready(Ok(guard)) => guard,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(io_to_errno(e))),
+                Poll::Pending => return Poll::Pending,
+            };
+            guard.get_inner().clear();
+            match op(&mut self.socket) {
+                Err(ref e) if would_block(*e) => guard.clear_ready(),
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+}
+
+/// A listening one-to-one SCTP socket for use with tokio.
+///
+/// Must be created within a tokio runtime.  Like all sockets, it cannot outlive
+/// the `UsrSctp` it was created from.
+pub struct AsyncSctpListener<'a, T: 'a + Ip> {
+    inner: AsyncSocket<'a, T>,
+}
+
+impl<'a, T: 'a + Ip> AsyncSctpListener<'a, T> {
+    /// Create a one-to-one socket, bind it and listen on it
+    pub fn bind(sctp: &'a UsrSctp, addr: T::Addr, port: u16, backlog: i32)
+                -> Result<AsyncSctpListener<'a, T>, Errno>
+    {
+        let mut socket = sctp.socket::<T, OneToOne>()?;
+        socket.bind(addr, port)?;
+        socket.listen(backlog)?;
+        AsyncSctpListener::from_socket(socket)
+    }
+
+    /// Use a socket which is already listening
+    pub fn from_socket(socket: Socket<'a, T>) -> Result<AsyncSctpListener<'a, T>, Errno> {
+        Ok(AsyncSctpListener {
+            inner: AsyncSocket::new(socket)?,
+        })
+    }
+
+    /// Accept a connection
+    pub fn accept<'s>(&'s mut self) -> Accept<'s, 'a, T> {
+        Accept { listener: self }
+    }
+
+    pub fn get_ref(&self) -> &Socket<'a, T> {
+        &self.inner.socket
+    }
+
+    pub fn get_mut(&mut self) -> &mut Socket<'a, T> {
+        &mut self.inner.socket
+    }
+}
+
+/// Future returned by `AsyncSctpListener::accept`
+pub struct Accept<'s, 'a: 's, T: 'a + Ip> {
+    listener: &'s mut AsyncSctpListener<'a, T>,
+}
+
+impl<'s, 'a: 's, T: 'a + Ip> Future for Accept<'s, 'a, T> {
+    type Output = Result<(T::Addr, u16, AsyncSctpStream<'a, T>), Errno>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        match this.listener.inner.poll_op(cx, |socket| socket.accept()) {
+            Poll::Ready(Ok((addr, port, socket))) => {
+                Poll::Ready(AsyncSctpStream::from_socket(socket)
+                            .map(|stream| (addr, port, stream)))
+            },
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// A connected one-to-one SCTP socket for use with tokio.
+///
+/// Must be created within a tokio runtime.  Like all sockets, it cannot outlive
+/// the `UsrSctp` it was created from.
+pub struct AsyncSctpStream<'a, T: 'a + Ip> {
+    inner: AsyncSocket<'a, T>,
+}
+
+impl<'a, T: 'a + Ip> AsyncSctpStream<'a, T> {
+    /// Connect to a server.  The returned future resolves once the association
+    /// is up (COMMUNICATION_UP).  The socket is only created when the future is
+    /// first polled, so this may be called outside the runtime.
+    pub fn connect(sctp: &'a UsrSctp, addr: T::Addr, port: u16) -> Connect<'a, T> {
+        Connect {
+            state: ConnectState::Start(sctp, addr, port),
+        }
+    }
+
+    /// Use a socket which is already connected
+    pub fn from_socket(socket: Socket<'a, T>) -> Result<AsyncSctpStream<'a, T>, Errno> {
+        Ok(AsyncSctpStream {
+            inner: AsyncSocket::new(socket)?,
+        })
+    }
+
+    /// Send a message (on the stream and with the PPID of `snd_info`, if given).
+    /// Resolves to the number of bytes sent.
+    pub fn send<'s, 'm>(&'s mut self, data: &'m [u8], snd_info: Option<SndInfo>)
+                        -> SendMsg<'s, 'm, 'a, T>
+    {
+        SendMsg { stream: self, data: data, snd_info: snd_info }
+    }
+
+    /// Receive a message (or part of one).  Resolves to the same as
+    /// `Socket::recvv`.
+    pub fn recv<'s, 'm>(&'s mut self, data: &'m mut [u8]) -> RecvMsg<'s, 'm, 'a, T> {
+        RecvMsg { stream: self, data: data }
+    }
+
+    pub fn get_ref(&self) -> &Socket<'a, T> {
+        &self.inner.socket
+    }
+
+    pub fn get_mut(&mut self) -> &mut Socket<'a, T> {
+        &mut self.inner.socket
+    }
+
+    // Try a socket operation, waiting for an upcall (and returning Pending) if it
+    // would block
+    pub(crate) fn poll_op<R, F>(&mut self, cx: &mut Context, op: F) -> Poll<Result<R, Errno>>
+        where F: FnMut(&mut Socket<'a, T>) -> Result<R, Errno>
+    {
+        self.inner.poll_op(cx, op)
+    }
+}
+
+/// Future returned by `AsyncSctpStream::connect`
+pub struct Connect<'a, T: 'a + Ip> {
+    state: ConnectState<'a, T>,
+}
+
+enum ConnectState<'a, T: 'a + Ip> {
+    // Not yet polled, so not yet within the runtime
+    Start(&'a UsrSctp, T::Addr, u16),
+    Connecting(AsyncSocket<'a, T>),
+    Done,
+}
+
+// Create the socket and start connecting.  This registers with the reactor, so
+// must happen within the runtime.
+fn start_connect<'a, T: 'a + Ip>(sctp: &'a UsrSctp, addr: T::Addr, port: u16)
+                                 -> Result<AsyncSocket<'a, T>, Errno>
+{
+    let mut inner = AsyncSocket::new(sctp.socket::<T, OneToOne>()?)?;
+    inner.socket.subscribe(SCTP_FUTURE_ASSOC, EventType::AssocChange, true)?;
+    match inner.socket.connect(addr, port) {
+        Err(ref e) if !would_block(*e) => Err(*e),
+        _ => Ok(inner),
+    }
+}
+
+impl<'a, T: 'a + Ip> Future for Connect<'a, T> {
+    type Output = Result<AsyncSctpStream<'a, T>, Errno>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let ConnectState::Start(sctp, addr, port) = this.state {
+            match start_connect(sctp, addr, port) {
+                Ok(inner) => this.state = ConnectState::Connecting(inner),
+                Err(e) => {
+                    this.state = ConnectState::Done;
+                    return Poll::Ready(Err(e));
+                },
+            }
+        }
+        let result = {
+            let inner = match this.state {
+                ConnectState::Connecting(ref mut inner) => inner,
+                _ => panic!("Connect polled after completion"),
+            };
+            // Wait for the association change notification
+            let mut buf = [0u8; 256];
+            inner.poll_op(cx, |socket| {
+                loop {
+                    let (len, _, _, flags) = socket.recvv(&mut buf)?;
+                    if !flags.contains(MsgFlags::NOTIFICATION) {
+                        continue; // can not be user data before the association is up
+                    }
+                    if let Some(Notification::AssocChange(ev)) = Notification::parse(&buf[..len]) {
+                        return match ev.state {
+                            AssocChangeState::CommUp => Ok(()),
+                            // The socket holds why (ETIMEDOUT when the INITs went
+                            // unanswered, ECONNREFUSED when the peer aborted)
+                            AssocChangeState::CantStartAssoc => {
+                                Err(socket.socket_error().unwrap_or(Errno(libc::ECONNREFUSED)))
+                            },
+                            _ => Err(Errno(libc::ECONNABORTED)),
+                        };
+                    }
+                }
+            })
+        };
+        match result {
+            Poll::Ready(Ok(())) => {
+                let mut inner = match mem::replace(&mut this.state, ConnectState::Done) {
+                    ConnectState::Connecting(inner) => inner,
+                    _ => unreachable!(),
+                };
+                // Notifications were only wanted while connecting
+                Poll::Ready(inner.socket.subscribe(SCTP_FUTURE_ASSOC, EventType::AssocChange, false)
+                            .map(|_| AsyncSctpStream { inner: inner }))
+            },
+            Poll::Ready(Err(e)) => {
+                this.state = ConnectState::Done;
+                Poll::Ready(Err(e))
+            },
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Future returned by `AsyncSctpStream::send`
+pub struct SendMsg<'s, 'm, 'a: 's, T: 'a + Ip> {
+    stream: &'s mut AsyncSctpStream<'a, T>,
+    data: &'m [u8],
+    snd_info: Option<SndInfo>,
+}
+
+impl<'s, 'm, 'a: 's, T: 'a + Ip> Future for SendMsg<'s, 'm, 'a, T> {
+    type Output = Result<usize, Errno>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let data = this.data;
+        let snd_info = this.snd_info;
+        this.stream.inner.poll_op(cx, |socket| {
+            socket.sendv(data, None, snd_info, None, None, MsgFlags::empty())
+        })
+    }
+}
+
+/// Future returned by `AsyncSctpStream::recv`
+pub struct RecvMsg<'s, 'm, 'a: 's, T: 'a + Ip> {
+    stream: &'s mut AsyncSctpStream<'a, T>,
+    data: &'m mut [u8],
+}
+
+impl<'s, 'm, 'a: 's, T: 'a + Ip> Future for RecvMsg<'s, 'm, 'a, T> {
+    type Output = Result<(usize, Option<(T::Addr, u16)>, Option<RcvInfo>, MsgFlags), Errno>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let data = &mut *this.data;
+        this.stream.inner.poll_op(cx, |socket| socket.recvv(data))
+    }
+}
@@ -0,0 +1,160 @@
+
+use errno::Errno;
+use rusrsctp_sys::*;
+use super::{Socket, Ip, Mode, AssocId, OptBuf, AuthActiveKey, AuthDeactivateKey, AuthDeleteKey,
+            AuthChunk, AuthSupported};
+
+/// HMAC algorithms usable with SCTP-AUTH (RFC 4895 section 3.3)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HmacAlgo {
+    /// HMAC-SHA-1 (mandatory to implement)
+    Sha1,
+    /// HMAC-SHA-256
+    Sha256,
+}
+
+impl HmacAlgo {
+    pub fn ident(&self) -> u16 {
+        (match *self {
+            HmacAlgo::Sha1 => SCTP_AUTH_HMAC_ID_SHA1,
+            HmacAlgo::Sha256 => SCTP_AUTH_HMAC_ID_SHA256,
+        }) as u16
+    }
+
+    pub fn from_ident(ident: u16) -> Option<HmacAlgo> {
+        match ident as u32 {
+            SCTP_AUTH_HMAC_ID_SHA1 => Some(HmacAlgo::Sha1),
+            SCTP_AUTH_HMAC_ID_SHA256 => Some(HmacAlgo::Sha256),
+            _ => None,
+        }
+    }
+}
+
+/// SCTP-AUTH (RFC 4895) key management.
+///
+/// Keys are identified by a key number, and are installed either on the endpoint
+/// (use SCTP_FUTURE_ASSOC as `assoc_id`, affecting associations created afterwards)
+/// or on an existing association.  Key number 0 with an empty secret is the
+/// default null key.  Subscribe to `EventType::Authentication` to learn when the
+/// peer starts using a new key, and when a deactivated key may be deleted
+/// (`AuthIndication::FreeKey`).
+impl<'a, T: 'a + Ip, M: Mode> Socket<'a, T, M> {
+    /// Enable or disable SCTP-AUTH for associations created afterwards.  ASCONF
+    /// needs it, so it can only be disabled with ASCONF disabled too (see
+    /// `set_asconf_supported`).
+    pub fn set_auth_supported(&mut self, on: bool) -> Result<(), Errno> {
+        self.setsockopt(AuthSupported(sctp_assoc_value {
+            assoc_id: SCTP_FUTURE_ASSOC,
+            assoc_value: if on { 1 } else { 0 },
+        }))
+    }
+
+    /// Whether SCTP-AUTH is in use on an association (i.e. both sides support
+    /// it).  Use SCTP_FUTURE_ASSOC to check whether we offer it.
+    pub fn get_auth_supported(&mut self, assoc_id: AssocId) -> Result<bool, Errno> {
+        let opt = self.getsockopt(AuthSupported(sctp_assoc_value {
+            assoc_id: assoc_id,
+            assoc_value: 0,
+        }))?;
+        Ok(opt.0.assoc_value != 0)
+    }
+
+    /// Add a shared key (or replace the secret of an existing key number)
+    pub fn add_auth_key(&mut self, assoc_id: AssocId, keynumber: u16, secret: &[u8])
+                        -> Result<(), Errno>
+    {
+        let mut buf = OptBuf::new::<sctp_authkey>(secret.len());
+        {
+            let key = buf.header_mut::<sctp_authkey>();
+            key.sca_assoc_id = assoc_id;
+            key.sca_keynumber = keynumber;
+            key.sca_keylength = secret.len() as u16;
+        }
+        buf.tail_mut::<sctp_authkey, u8>().copy_from_slice(secret);
+        self.setsockopt_buf(SCTP_AUTH_KEY, &buf)
+    }
+
+    /// Make a key the one used for sending
+    pub fn set_active_key(&mut self, assoc_id: AssocId, keynumber: u16) -> Result<(), Errno> {
+        self.setsockopt(AuthActiveKey(sctp_authkeyid {
+            scact_assoc_id: assoc_id,
+            scact_keynumber: keynumber,
+        }))
+    }
+
+    /// Get the key number of the key used for sending
+    pub fn get_active_key(&mut self, assoc_id: AssocId) -> Result<u16, Errno> {
+        let key = self.getsockopt(AuthActiveKey(sctp_authkeyid {
+            scact_assoc_id: assoc_id,
+            scact_keynumber: 0,
+        }))?;
+        Ok(key.0.scact_keynumber)
+    }
+
+    /// Stop using a key for sending.  It remains usable for receiving until an
+    /// `AuthIndication::FreeKey` notification says the peer no longer uses it.
+    /// The active key cannot be deactivated.
+    pub fn deactivate_key(&mut self, assoc_id: AssocId, keynumber: u16) -> Result<(), Errno> {
+        self.setsockopt(AuthDeactivateKey(sctp_authkeyid {
+            scact_assoc_id: assoc_id,
+            scact_keynumber: keynumber,
+        }))
+    }
+
+    /// Delete a deactivated key
+    pub fn delete_key(&mut self, assoc_id: AssocId, keynumber: u16) -> Result<(), Errno> {
+        self.setsockopt(AuthDeleteKey(sctp_authkeyid {
+            scact_assoc_id: assoc_id,
+            scact_keynumber: keynumber,
+        }))
+    }
+
+    /// Set the HMAC algorithms acceptable to us, in order of preference.
+    /// SHA-1 must be among them.
+    pub fn set_hmac_algos(&mut self, algos: &[HmacAlgo]) -> Result<(), Errno> {
+        let mut buf = OptBuf::new::<sctp_hmacalgo>(algos.len() * 2);
+        buf.header_mut::<sctp_hmacalgo>().shmac_number_of_idents = algos.len() as u32;
+        for (ident, algo) in buf.tail_mut::<sctp_hmacalgo, u16>().iter_mut().zip(algos) {
+            *ident = algo.ident();
+        }
+        self.setsockopt_buf(SCTP_HMAC_IDENT, &buf)
+    }
+
+    /// Get the HMAC algorithms acceptable to us, in order of preference
+    pub fn get_hmac_algos(&mut self) -> Result<Vec<HmacAlgo>, Errno> {
+        // There are only a handful of HMAC identifiers defined
+        let mut buf = OptBuf::new::<sctp_hmacalgo>(16 * 2);
+        self.getsockopt_buf(SCTP_HMAC_IDENT, &mut buf)?;
+        let count = buf.header::<sctp_hmacalgo>().shmac_number_of_idents as usize;
+        Ok(buf.tail::<sctp_hmacalgo, u16>().iter()
+           .take(count)
+           .filter_map(|ident| HmacAlgo::from_ident(*ident))
+           .collect())
+    }
+
+    /// Require that the peer authenticate chunks of the given type.  This must
+    /// be done before the association is set up.
+    pub fn add_auth_chunk(&mut self, chunk_type: u8) -> Result<(), Errno> {
+        self.setsockopt(AuthChunk(sctp_authchunk {
+            sauth_chunk: chunk_type,
+        }))
+    }
+
+    /// The chunk types the peer requires us to authenticate
+    pub fn peer_auth_chunks(&mut self, assoc_id: AssocId) -> Result<Vec<u8>, Errno> {
+        self.auth_chunks(SCTP_PEER_AUTH_CHUNKS, assoc_id)
+    }
+
+    /// The chunk types we require the peer to authenticate
+    pub fn local_auth_chunks(&mut self, assoc_id: AssocId) -> Result<Vec<u8>, Errno> {
+        self.auth_chunks(SCTP_LOCAL_AUTH_CHUNKS, assoc_id)
+    }
+
+    fn auth_chunks(&mut self, c_name: u32, assoc_id: AssocId) -> Result<Vec<u8>, Errno> {
+        // Chunk types are a u8, so there can be at most 256
+        let mut buf = OptBuf::new::<sctp_authchunks>(256);
+        buf.header_mut::<sctp_authchunks>().gauth_assoc_id = assoc_id;
+        self.getsockopt_buf(c_name, &mut buf)?;
+        Ok(buf.tail::<sctp_authchunks, u8>().to_vec())
+    }
+}
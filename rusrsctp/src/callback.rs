@@ -0,0 +1,241 @@
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::os::raw::{c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use errno::{self, Errno};
+use libc;
+use rusrsctp_sys::*;
+use super::{UsrSctp, Socket, Ip, Mode, RcvInfo, MsgFlags, RecvBuf};
+
+/// A callback for when the send buffer has room again.  The callback is given the
+/// number of bytes free in the send buffer, and is called when that number rises
+/// to at least `sb_threshold` (or whenever it rises, if `sb_threshold` is 0).
+pub struct SendCallback {
+    pub sb_threshold: u32,
+    pub callback: Box<dyn FnMut(u32) + Send>,
+}
+
+impl SendCallback {
+    pub fn new<F: FnMut(u32) + Send + 'static>(sb_threshold: u32, callback: F) -> SendCallback {
+        SendCallback {
+            sb_threshold: sb_threshold,
+            callback: Box::new(callback),
+        }
+    }
+}
+
+type ReceiveFn<T> = dyn FnMut(&[u8], Option<(<T as Ip>::Addr, u16)>, RcvInfo, MsgFlags) + Send;
+type ReceiveBufFn<T> = dyn FnMut(RecvBuf, Option<(<T as Ip>::Addr, u16)>, RcvInfo, MsgFlags) + Send;
+
+// Whether the receive callback borrows the data (which is then freed), or takes it
+enum Receive<T: Ip> {
+    Borrowed(Mutex<Box<ReceiveFn<T>>>),
+    Owned(Mutex<Box<ReceiveBufFn<T>>>),
+}
+
+type Registry = HashMap<usize, Weak<dyn Any + Send + Sync>>;
+
+// The callbacks of each callback mode socket, by the id handed to usrsctp as
+// ulp_info.  usrsctp may call back on its own threads at any time, even after
+// the sockets are gone (associations not yet accepted inherit the listener's
+// ulp_info, and outlive it), so the trampolines look their callbacks up here
+// rather than trusting ulp_info as a pointer.
+static CALLBACKS: Mutex<Option<Registry>> = Mutex::new(None);
+// Ids are just unique numbers; 0 is never used, as it reads as a null ulp_info
+static NEXT_CALLBACKS_ID: AtomicUsize = AtomicUsize::new(1);
+
+// The callbacks of a socket.  Sockets accepted from a callback mode socket share
+// them (usrsctp copies ulp_info to them), so they are reference counted by the
+// sockets using them, and deregistered when the last of those is dropped.  A
+// callback already running holds a reference of its own.
+pub(crate) struct Callbacks<T: Ip> {
+    id: usize,
+    receive: Receive<T>,
+    send: Option<Mutex<Box<dyn FnMut(u32) + Send>>>,
+}
+
+impl<T: 'static + Ip> Callbacks<T> {
+    fn register(receive: Receive<T>, send: Option<Mutex<Box<dyn FnMut(u32) + Send>>>)
+                -> Arc<Callbacks<T>>
+    {
+        let callbacks = Arc::new(Callbacks {
+            id: NEXT_CALLBACKS_ID.fetch_add(1, Ordering::SeqCst),
+            receive: receive,
+            send: send,
+        });
+        let any: Arc<dyn Any + Send + Sync> = callbacks.clone();
+        let mut registry = CALLBACKS.lock().unwrap_or_else(|e| e.into_inner());
+        registry.get_or_insert_with(HashMap::new).insert(callbacks.id, Arc::downgrade(&any));
+        callbacks
+    }
+
+    // The callbacks registered under ulp_info, if any socket still uses them
+    fn lookup(ulp_info: *mut c_void) -> Option<Arc<Callbacks<T>>> {
+        let any = {
+            let registry = CALLBACKS.lock().unwrap_or_else(|e| e.into_inner());
+            registry.as_ref()
+                .and_then(|r| r.get(&(ulp_info as usize)))
+                .and_then(|weak| weak.upgrade())
+        };
+        any.and_then(|any| any.downcast::<Callbacks<T>>().ok())
+    }
+}
+
+impl<T: Ip> Callbacks<T> {
+    pub(crate) fn ulp_info(this: &Arc<Callbacks<T>>) -> *mut c_void {
+        this.id as *mut c_void
+    }
+}
+
+impl<T: Ip> Drop for Callbacks<T> {
+    fn drop(&mut self) {
+        let mut registry = CALLBACKS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(ref mut registry) = *registry {
+            registry.remove(&self.id);
+        }
+    }
+}
+
+impl UsrSctp {
+    /// Create a socket in callback mode.
+    ///
+    /// Instead of calling `recvv`, incoming messages (and notifications, if
+    /// `MsgFlags::NOTIFICATION` is set) are passed to `on_receive` along with the
+    /// sender's address, the receive info, and the message flags.  Empty data
+    /// means the association has closed.  `on_send`, if given, is called when
+    /// there is room in the send buffer.
+    ///
+    /// The callbacks are called on usrsctp's own threads.  If a callback panics,
+    /// the panic is caught (it must not unwind into C) and the data is dropped.
+    /// Sockets accepted from this socket share its callbacks.
+    pub fn socket_with_callbacks<'a, T, M, R>(&'a self, on_receive: R,
+                                              on_send: Option<SendCallback>)
+                                              -> Result<Socket<'a, T, M>, Errno>
+        where T: 'static + Ip,
+              M: Mode,
+              R: FnMut(&[u8], Option<(T::Addr, u16)>, RcvInfo, MsgFlags) + Send + 'static
+    {
+        self.callback_socket(Receive::Borrowed(Mutex::new(Box::new(on_receive) as Box<ReceiveFn<T>>)),
+                             on_send)
+    }
+
+    /// Create a socket in callback mode which hands over received data without
+    /// copying it.
+    ///
+    /// This is like `socket_with_callbacks`, except that `on_receive` is given
+    /// the buffer usrsctp received the data into, to keep for as long as it
+    /// likes.  The buffer is freed when it is dropped.
+    pub fn socket_with_buffer_callbacks<'a, T, M, R>(&'a self, on_receive: R,
+                                                     on_send: Option<SendCallback>)
+                                                     -> Result<Socket<'a, T, M>, Errno>
+        where T: 'static + Ip,
+              M: Mode,
+              R: FnMut(RecvBuf, Option<(T::Addr, u16)>, RcvInfo, MsgFlags) + Send + 'static
+    {
+        self.callback_socket(Receive::Owned(Mutex::new(Box::new(on_receive) as Box<ReceiveBufFn<T>>)),
+                             on_send)
+    }
+
+    fn callback_socket<'a, T: 'static + Ip, M: Mode>(&'a self, receive: Receive<T>,
+                                                     on_send: Option<SendCallback>)
+                                                     -> Result<Socket<'a, T, M>, Errno>
+    {
+        let sb_threshold = on_send.as_ref().map(|s| s.sb_threshold).unwrap_or(0);
+        let has_send = on_send.is_some();
+        let callbacks = Callbacks::register(receive, on_send.map(|s| Mutex::new(s.callback)));
+        let so = unsafe {
+            usrsctp_socket(
+                T::pf(),
+                M::sock_type(), // type
+                IPPROTO_SCTP as i32,
+                Some(receive_trampoline::<T>),
+                if has_send { Some(send_trampoline::<T>) } else { None },
+                sb_threshold,
+                Callbacks::ulp_info(&callbacks)
+            )
+        };
+        if so.is_null() {
+            Err(errno::errno())
+        } else {
+            let mut socket = Socket {
+                inner: so,
+                callbacks: Some(callbacks),
+                readiness: None,
+                _ip: PhantomData,
+                _mode: PhantomData,
+            };
+            socket.init_family()?;
+            Ok(socket)
+        }
+    }
+}
+
+unsafe extern "C" fn receive_trampoline<T: 'static + Ip>(_sock: *mut socket,
+                                                         addr: sctp_sockstore,
+                                                         data: *mut c_void, datalen: usize,
+                                                         rcv: sctp_rcvinfo, flags: c_int,
+                                                         ulp_info: *mut c_void) -> c_int
+{
+    // Nothing is registered once every socket using the callbacks is dropped
+    if let Some(callbacks) = Callbacks::<T>::lookup(ulp_info) {
+        let from = from_sockstore::<T>(&addr);
+        let rcv = RcvInfo::from_sctp_rcvinfo(rcv);
+        let flags = MsgFlags::from_bits_truncate(flags as u32);
+        match callbacks.receive {
+            Receive::Borrowed(ref receive) => {
+                let bytes = if data.is_null() {
+                    &[][..]
+                } else {
+                    slice::from_raw_parts(data as *const u8, datalen)
+                };
+                let mut receive = receive.lock().unwrap_or_else(|e| e.into_inner());
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+                    (*receive)(bytes, from, rcv, flags)
+                }));
+            },
+            Receive::Owned(ref receive) => {
+                // the buffer now belongs to the callback (and is freed if it panics)
+                let buf = RecvBuf::from_raw(data as *mut u8, datalen);
+                let mut receive = receive.lock().unwrap_or_else(|e| e.into_inner());
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+                    (*receive)(buf, from, rcv, flags)
+                }));
+                return 1;
+            },
+        }
+    }
+    // usrsctp hands received data to the receive callback in a malloc()ed buffer
+    if !data.is_null() {
+        libc::free(data as *mut libc::c_void);
+    }
+    1
+}
+
+unsafe extern "C" fn send_trampoline<T: 'static + Ip>(_sock: *mut socket, sb_free: u32,
+                                                      ulp_info: *mut c_void) -> c_int
+{
+    if let Some(callbacks) = Callbacks::<T>::lookup(ulp_info) {
+        if let Some(ref send) = callbacks.send {
+            let mut send = send.lock().unwrap_or_else(|e| e.into_inner());
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+                (*send)(sb_free)
+            }));
+        }
+    }
+    1
+}
+
+pub(crate) unsafe fn from_sockstore<T: Ip>(addr: &sctp_sockstore) -> Option<(T::Addr, u16)> {
+    let sa = &*(addr as *const sctp_sockstore as *const sockaddr);
+    if T::has_family(sa.sa_family as u32) {
+        Some(T::from_sockaddr(ptr::read(addr as *const sctp_sockstore as *const T::Sockaddr)))
+    } else {
+        None
+    }
+}
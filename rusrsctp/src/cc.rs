@@ -0,0 +1,116 @@
+
+use errno::Errno;
+use rusrsctp_sys::*;
+use super::{Socket, Ip, Mode, AssocId, PluggableCc, CcOption};
+
+/// Congestion control modules shipped with usrsctp
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CongestionControl {
+    /// Standard SCTP congestion control (RFC 2581 / RFC 4960 section 7).  The default.
+    Rfc2581,
+    /// HighSpeed TCP (RFC 3649), for large bandwidth-delay products
+    HsTcp,
+    /// H-TCP, for large bandwidth-delay products
+    HTcp,
+    /// RTCC, which backs off when the round trip time grows (data center
+    /// congestion control)
+    Rtcc,
+}
+
+impl CongestionControl {
+    pub fn c_value(&self) -> u32 {
+        match *self {
+            CongestionControl::Rfc2581 => SCTP_CC_RFC2581,
+            CongestionControl::HsTcp => SCTP_CC_HSTCP,
+            CongestionControl::HTcp => SCTP_CC_HTCP,
+            CongestionControl::Rtcc => SCTP_CC_RTCC,
+        }
+    }
+
+    pub fn from_c_value(value: u32) -> Option<CongestionControl> {
+        match value {
+            SCTP_CC_RFC2581 => Some(CongestionControl::Rfc2581),
+            SCTP_CC_HSTCP => Some(CongestionControl::HsTcp),
+            SCTP_CC_HTCP => Some(CongestionControl::HTcp),
+            SCTP_CC_RTCC => Some(CongestionControl::Rtcc),
+            _ => None,
+        }
+    }
+}
+
+/// Congestion control module parameters.  These only apply to `CongestionControl::Rtcc`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CcParameter {
+    /// Nonzero turns on RTT based back off
+    RtccSetMode,
+    /// Nonzero uses ECN data center congestion control
+    UseDcccEcn,
+    /// Number of steady state steps before probing for more bandwidth
+    SteadyStep,
+}
+
+impl CcParameter {
+    pub fn c_value(&self) -> i32 {
+        (match *self {
+            CcParameter::RtccSetMode => SCTP_CC_OPT_RTCC_SETMODE,
+            CcParameter::UseDcccEcn => SCTP_CC_OPT_USE_DCCC_ECN,
+            CcParameter::SteadyStep => SCTP_CC_OPT_STEADY_STEP,
+        }) as i32
+    }
+}
+
+/// Congestion control.
+///
+/// The module can be chosen for the endpoint (use SCTP_FUTURE_ASSOC as
+/// `assoc_id`, affecting associations created afterwards) or changed on an
+/// existing association.  Module parameters apply to existing associations only.
+impl<'a, T: 'a + Ip, M: Mode> Socket<'a, T, M> {
+    /// Select the congestion control module
+    pub fn set_congestion_control(&mut self, assoc_id: AssocId, cc: CongestionControl)
+                                  -> Result<(), Errno>
+    {
+        self.setsockopt(PluggableCc(sctp_assoc_value {
+            assoc_id: assoc_id,
+            assoc_value: cc.c_value(),
+        }))
+    }
+
+    /// Get the congestion control module in use.  Returns None if usrsctp reports
+    /// a module unknown to this library.
+    pub fn get_congestion_control(&mut self, assoc_id: AssocId)
+                                  -> Result<Option<CongestionControl>, Errno>
+    {
+        let cc = self.getsockopt(PluggableCc(sctp_assoc_value {
+            assoc_id: assoc_id,
+            assoc_value: 0,
+        }))?;
+        Ok(CongestionControl::from_c_value(cc.0.assoc_value))
+    }
+
+    /// Set a congestion control module parameter on an association
+    pub fn set_cc_parameter(&mut self, assoc_id: AssocId, param: CcParameter, value: u32)
+                            -> Result<(), Errno>
+    {
+        self.setsockopt(CcOption(sctp_cc_option {
+            option: param.c_value(),
+            aid_value: sctp_assoc_value {
+                assoc_id: assoc_id,
+                assoc_value: value,
+            },
+        }))
+    }
+
+    /// Get a congestion control module parameter of an association
+    pub fn get_cc_parameter(&mut self, assoc_id: AssocId, param: CcParameter)
+                            -> Result<u32, Errno>
+    {
+        let opt = self.getsockopt(CcOption(sctp_cc_option {
+            option: param.c_value(),
+            aid_value: sctp_assoc_value {
+                assoc_id: assoc_id,
+                assoc_value: 0,
+            },
+        }))?;
+        Ok(opt.0.aid_value.assoc_value)
+    }
+}
@@ -0,0 +1,88 @@
+
+use std::collections::HashMap;
+use std::os::raw::{c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::slice;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use rusrsctp_sys::*;
+use super::{UsrSctp, ConnAddr};
+
+type OutputFn = Mutex<Box<dyn FnMut(&[u8]) + Send>>;
+
+// The output function of each registered AF_CONN address
+static CONN_OUTPUTS: Mutex<Option<HashMap<usize, Arc<OutputFn>>>> = Mutex::new(None);
+// Addresses are just unique numbers; 0 would be the wildcard
+static NEXT_CONN_ADDR: AtomicUsize = AtomicUsize::new(1);
+
+// The conn_output function handed to usrsctp_init for all AF_CONN addresses
+pub(crate) unsafe extern "C" fn conn_output(addr: *mut c_void, buffer: *mut c_void,
+                                            length: usize, _tos: u8, _set_df: u8) -> c_int
+{
+    // Do not hold the registry lock while calling out, as the output function may
+    // well register or deregister addresses itself
+    let output = {
+        let outputs = CONN_OUTPUTS.lock().unwrap_or_else(|e| e.into_inner());
+        outputs.as_ref().and_then(|o| o.get(&(addr as usize)).cloned())
+    };
+    match output {
+        Some(output) => {
+            let packet = slice::from_raw_parts(buffer as *const u8, length);
+            let mut output = output.lock().unwrap_or_else(|e| e.into_inner());
+            match panic::catch_unwind(AssertUnwindSafe(|| (*output)(packet))) {
+                Ok(()) => 0,
+                Err(_) => EIO as c_int,
+            }
+        },
+        None => EHOSTUNREACH as c_int,
+    }
+}
+
+/// AF_CONN links, over which usrsctp hands packets to the application rather
+/// than sending them itself.  Use these with `Socket<Conn>`.
+impl UsrSctp {
+    /// Register a new lower layer link.  `output` is called with each SCTP
+    /// packet to be sent over it, which the application must deliver to the
+    /// other end (e.g. inside DTLS), where it is passed to `conn_input`.
+    /// Without `new_nothreads`, `output` is called on usrsctp's own threads.
+    pub fn register_conn<F>(&self, output: F) -> ConnAddr
+        where F: FnMut(&[u8]) + Send + 'static
+    {
+        let addr = NEXT_CONN_ADDR.fetch_add(1, Ordering::SeqCst);
+        {
+            let mut outputs = CONN_OUTPUTS.lock().unwrap_or_else(|e| e.into_inner());
+            outputs.get_or_insert_with(HashMap::new)
+                .insert(addr, Arc::new(Mutex::new(Box::new(output) as Box<dyn FnMut(&[u8]) + Send>)));
+        }
+        unsafe {
+            usrsctp_register_address(addr as *mut c_void);
+        }
+        ConnAddr(addr)
+    }
+
+    /// Deregister a link registered with `register_conn`.  Associations over it
+    /// can no longer send.
+    pub fn deregister_conn(&self, addr: ConnAddr) {
+        unsafe {
+            usrsctp_deregister_address(addr.0 as *mut c_void);
+        }
+        let mut outputs = CONN_OUTPUTS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(ref mut outputs) = *outputs {
+            outputs.remove(&addr.0);
+        }
+    }
+
+    /// Pass a packet received over the link `addr` to SCTP
+    pub fn conn_input(&self, addr: ConnAddr, packet: &[u8]) {
+        conn_input(addr, packet)
+    }
+}
+
+// For transports whose reader threads can not borrow the UsrSctp.  They must
+// stop before it is dropped.
+pub(crate) fn conn_input(addr: ConnAddr, packet: &[u8]) {
+    unsafe {
+        usrsctp_conninput(addr.0 as *mut c_void, packet.as_ptr() as *const c_void,
+                          packet.len(), 0);
+    }
+}
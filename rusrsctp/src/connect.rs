@@ -0,0 +1,240 @@
+
+use std::mem;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use std::os::raw::{c_int, c_void};
+use std::os::unix::io::AsRawFd;
+use errno::{self, Errno};
+use libc;
+use rusrsctp_sys::*;
+use super::{Socket, Ip, Mode, OneToOne, OneToMany, AssocId, InitMsg, Status, SndInfo, SctpFlags,
+            MsgFlags, Readiness, NO_THREADS};
+
+// How often connect_timeout checks on a one-to-many association, which usrsctp
+// only signals coming up (or failing) with a notification, if subscribed to
+const ONE_TO_MANY_RECHECK: Duration = Duration::from_millis(10);
+
+/// How new associations are set up (SCTP_INITMSG).  Zero leaves a field at the
+/// endpoint's current setting.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct InitParams {
+    /// Number of streams we would like to send on
+    pub num_ostreams: u16,
+    /// Most streams we allow the peer to send on
+    pub max_instreams: u16,
+    /// How many times an INIT is sent before giving up on the association
+    pub max_attempts: u16,
+    /// Most time (in milliseconds) to wait for an answer to one INIT
+    pub max_init_timeout: u16,
+}
+
+impl InitParams {
+    #[inline]
+    pub fn into_sctp_initmsg(self) -> sctp_initmsg {
+        sctp_initmsg {
+            sinit_num_ostreams: self.num_ostreams,
+            sinit_max_instreams: self.max_instreams,
+            sinit_max_attempts: self.max_attempts,
+            sinit_max_init_timeo: self.max_init_timeout,
+        }
+    }
+}
+
+// Wait up to `timeout` for the eventfd of `readiness` to become readable
+fn wait_readable(readiness: &Readiness, timeout: Duration) -> Result<(), Errno> {
+    let mut pollfd = libc::pollfd {
+        fd: readiness.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // Round up, so as not to wake just before the deadline
+    let millis = (timeout.as_secs() * 1000 + (timeout.subsec_nanos() as u64 + 999_999) / 1_000_000)
+        .min(c_int::max_value() as u64) as c_int;
+    let rval = unsafe {
+        libc::poll(&mut pollfd, 1, millis)
+    };
+    if rval < 0 && errno::errno().0 != libc::EINTR {
+        Err(errno::errno())
+    } else {
+        Ok(())
+    }
+}
+
+impl<'a, T: 'a + Ip, M: Mode> Socket<'a, T, M> {
+    /// Set how associations created by later calls to `connect` are set up
+    pub fn set_init_params(&mut self, params: InitParams) -> Result<(), Errno> {
+        self.setsockopt(InitMsg(params.into_sctp_initmsg()))
+    }
+
+    // Run `connect` without blocking, then wait for the association it started
+    // (or for the deadline), leaving the socket as blocking or not as it was
+    fn connect_within<F>(&mut self, timeout: Duration, init: Option<InitParams>,
+                         recheck: Option<Duration>, connect: F) -> Result<AssocId, Errno>
+        where F: FnOnce(&mut Self) -> Result<AssocId, Errno>
+    {
+        // Without threads, nothing moves SCTP's clock (or feeds it packets) while
+        // this blocks
+        if NO_THREADS.load(Ordering::SeqCst) {
+            return Err(Errno(libc::EINVAL));
+        }
+        if let Some(init) = init {
+            self.set_init_params(init)?;
+        }
+        let non_blocking = self.get_non_blocking()?;
+        self.set_non_blocking(true)?;
+        let result = match connect(self) {
+            Ok(assoc_id) => self.wait_connected(assoc_id, Instant::now() + timeout, recheck),
+            Err(e) => Err(e),
+        };
+        // Why the connect failed matters more than this
+        let _ = self.set_non_blocking(non_blocking);
+        result
+    }
+
+    // Wait for upcalls until the association is up, setup fails or the deadline
+    // passes.  A readiness bridge already set on the socket is shared (and
+    // signalled afterwards, so its owner misses nothing); otherwise one is set
+    // just for this.
+    fn wait_connected(&mut self, assoc_id: AssocId, deadline: Instant, recheck: Option<Duration>)
+                      -> Result<AssocId, Errno>
+    {
+        let (readiness, own) = match self.readiness() {
+            Some(readiness) => (readiness.clone(), false),
+            None => (Readiness::new()?, true),
+        };
+        if own {
+            self.set_readiness(&readiness)?;
+        }
+        let result = self.wait_upcalls(&readiness, assoc_id, deadline, recheck);
+        if own {
+            let _ = self.clear_readiness();
+        } else {
+            readiness.signal();
+        }
+        result
+    }
+
+    fn wait_upcalls(&mut self, readiness: &Readiness, assoc_id: AssocId, deadline: Instant,
+                    recheck: Option<Duration>) -> Result<AssocId, Errno>
+    {
+        loop {
+            // Clear first, so an upcall while checking is not missed
+            readiness.clear();
+            if self.assoc_up(assoc_id, Errno(libc::ECONNREFUSED))? {
+                return Ok(assoc_id);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                // Stop retrying the INIT, so the socket may be used again
+                let abort = SndInfo {
+                    flags: SctpFlags::ABORT,
+                    assoc_id: assoc_id,
+                    ..Default::default()
+                };
+                let _ = self.sendv_inner(&[], None, Some(abort), None, None, MsgFlags::empty());
+                return Err(Errno(libc::ETIMEDOUT));
+            }
+            let wait = match recheck {
+                Some(recheck) if recheck < deadline - now => recheck,
+                _ => deadline - now,
+            };
+            wait_readable(readiness, wait)?;
+        }
+    }
+
+    // Whether an association is up yet, or why it could not be set up.  Only
+    // one-to-one sockets keep the reason (in SO_ERROR); `gone` is given when
+    // there is no association and no reason.
+    fn assoc_up(&mut self, assoc_id: AssocId, gone: Errno) -> Result<bool, Errno> {
+        let mut status: sctp_status = unsafe { mem::zeroed() };
+        status.sstat_assoc_id = assoc_id;
+        match self.getsockopt(Status(status)) {
+            Ok(Status(status)) => match status.sstat_state as u32 {
+                SCTP_COOKIE_WAIT | SCTP_COOKIE_ECHOED => Ok(false),
+                SCTP_CLOSED => Err(self.socket_error().unwrap_or(gone)),
+                _ => Ok(true),
+            },
+            // The association has gone (or never was)
+            Err(_) => Err(self.socket_error().unwrap_or(gone)),
+        }
+    }
+
+    // The pending error (SO_ERROR) of the socket, if any.  usrsctp does not clear
+    // it when it is read.
+    pub(crate) fn socket_error(&mut self) -> Option<Errno> {
+        let mut error: c_int = 0;
+        let mut len = mem::size_of::<c_int>() as u32;
+        let rval = unsafe {
+            usrsctp_getsockopt(
+                self.inner,
+                libc::SOL_SOCKET,
+                libc::SO_ERROR,
+                &mut error as *mut c_int as *mut c_void,
+                &mut len as *mut u32
+            )
+        };
+        if rval < 0 || error == 0 {
+            None
+        } else {
+            Some(Errno(error))
+        }
+    }
+}
+
+impl<'a, T: 'a + Ip> Socket<'a, T, OneToOne> {
+    /// Connect, giving up with ETIMEDOUT if the association is not up within
+    /// `timeout`.  `init` (if given) sets how hard the INIT is retried first,
+    /// which can end the attempt sooner.  The socket is left as blocking or
+    /// non-blocking as it was.
+    ///
+    /// This blocks, so it fails with EINVAL for SCTP started by `new_nothreads`,
+    /// whose clock and packets only move when the application moves them; start
+    /// the connect with `connect` on a non-blocking socket (or
+    /// `UnboundSocket::start_connect`) and check on it with `poll_connect` instead.
+    pub fn connect_timeout(&mut self, addr: T::Addr, port: u16, timeout: Duration,
+                           init: Option<InitParams>) -> Result<(), Errno>
+    {
+        // usrsctp signals a one-to-one socket when its association comes up or fails
+        self.connect_within(timeout, init, None, |socket| {
+            match socket.connect(addr, port) {
+                Ok(()) => Ok(0),
+                Err(ref e) if e.0 == libc::EINPROGRESS => Ok(0),
+                Err(e) => Err(e),
+            }
+        }).map(|_| ())
+    }
+
+    /// Check on a `connect` made without blocking (which returned EINPROGRESS).
+    /// Returns true once the association is up (SCTP_COMM_UP), and false while
+    /// it is still being set up.  Returns the error if setup failed
+    /// (SCTP_CANT_STR_ASSOC), e.g. ETIMEDOUT once the INITs (see
+    /// `set_init_params`) have all gone unanswered, or ECONNREFUSED.  Returns
+    /// ENOTCONN if the socket was never connected.
+    ///
+    /// This does not receive the association change notification, so it stays
+    /// for `recvv` if subscribed to.
+    pub fn poll_connect(&mut self) -> Result<bool, Errno> {
+        self.assoc_up(0, Errno(libc::ENOTCONN))
+    }
+}
+
+impl<'a, T: 'a + Ip> Socket<'a, T, OneToMany> {
+    /// Set up an association with `addr` and `port`, giving up with ETIMEDOUT if
+    /// it is not up within `timeout`, as `Socket::<T, OneToOne>::connect_timeout`
+    /// does (and, likewise, not for SCTP started by `new_nothreads`).  Returns
+    /// the id of the new association.
+    pub fn connect_timeout(&mut self, addr: T::Addr, port: u16, timeout: Duration,
+                           init: Option<InitParams>) -> Result<AssocId, Errno>
+    {
+        self.connect_within(timeout, init, Some(ONE_TO_MANY_RECHECK),
+                            |socket| socket.connectx(&[(addr, port)]))
+    }
+
+    /// Check on an association set up with `connectx`, as
+    /// `Socket::<T, OneToOne>::poll_connect` does.  One-to-many sockets do not
+    /// keep why setup failed, so that is ECONNREFUSED whatever the cause (the
+    /// SCTP_CANT_STR_ASSOC notification tells more).
+    pub fn poll_connect(&mut self, assoc_id: AssocId) -> Result<bool, Errno> {
+        self.assoc_up(assoc_id, Errno(libc::ECONNREFUSED))
+    }
+}
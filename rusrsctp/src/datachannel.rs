@@ -0,0 +1,439 @@
+
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+use errno::Errno;
+use libc;
+use rusrsctp_sys::*;
+use super::{htonl, ntohl, Socket, Conn, SndInfo, PrInfo, PrPolicy, SctpFlags, MsgFlags, RecvRcvInfo,
+            Status, EventType, Notification, StreamResetFlags, MessageAssembler, Message};
+
+/// PPID of Data Channel Establishment Protocol messages (RFC 8832)
+pub const PPID_DCEP: u32 = 50;
+/// PPID of a non-empty text message (RFC 8831)
+pub const PPID_STRING: u32 = 51;
+/// PPID of a partial binary message (deprecated; received as binary)
+pub const PPID_BINARY_PARTIAL: u32 = 52;
+/// PPID of a non-empty binary message
+pub const PPID_BINARY: u32 = 53;
+/// PPID of a partial text message (deprecated; received as text)
+pub const PPID_STRING_PARTIAL: u32 = 54;
+/// PPID of an empty text message (sent as a single zero byte)
+pub const PPID_STRING_EMPTY: u32 = 56;
+/// PPID of an empty binary message (sent as a single zero byte)
+pub const PPID_BINARY_EMPTY: u32 = 57;
+
+const DATA_CHANNEL_ACK: u8 = 0x02;
+const DATA_CHANNEL_OPEN: u8 = 0x03;
+
+const CHANNEL_RELIABLE: u8 = 0x00;
+const CHANNEL_PARTIAL_RELIABLE_REXMIT: u8 = 0x01;
+const CHANNEL_PARTIAL_RELIABLE_TIMED: u8 = 0x02;
+const CHANNEL_UNORDERED: u8 = 0x80;
+
+// The fixed part of DATA_CHANNEL_OPEN, before the label and protocol
+const OPEN_HEADER_LEN: usize = 12;
+
+// What browsers offer by default as their max-message-size
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 262144;
+
+// Big enough for DCEP messages, and most others in one go
+const RECV_CHUNK: usize = 65536;
+
+/// How hard a data channel tries to deliver its messages
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Reliability {
+    /// Deliver every message
+    Reliable,
+    /// Give up on a message after this many retransmissions
+    MaxRetransmits(u32),
+    /// Give up on a message after this many milliseconds
+    MaxPacketLifeTime(u32),
+}
+
+impl Reliability {
+    /// The partial reliability policy to send with
+    pub fn pr_info(&self) -> Option<PrInfo> {
+        match *self {
+            Reliability::Reliable => None,
+            Reliability::MaxRetransmits(n) => Some(PrInfo { policy: PrPolicy::Rtx, value: n }),
+            Reliability::MaxPacketLifeTime(ms) => Some(PrInfo { policy: PrPolicy::Ttl, value: ms }),
+        }
+    }
+}
+
+/// The properties of a data channel, as announced when it is opened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelConfig {
+    pub label: String,
+    /// The subprotocol, if any (empty if none)
+    pub protocol: String,
+    /// Whether messages are delivered in order
+    pub ordered: bool,
+    pub reliability: Reliability,
+    /// Priority, as in RFC 8831 (256 is normal)
+    pub priority: u16,
+}
+
+impl ChannelConfig {
+    /// An ordered, reliable channel of normal priority with no subprotocol
+    pub fn new(label: &str) -> ChannelConfig {
+        ChannelConfig {
+            label: label.to_owned(),
+            protocol: String::new(),
+            ordered: true,
+            reliability: Reliability::Reliable,
+            priority: 256,
+        }
+    }
+
+    // Encode as a DATA_CHANNEL_OPEN message
+    pub(crate) fn encode_open(&self) -> Vec<u8> {
+        let (channel_type, param) = match self.reliability {
+            Reliability::Reliable => (CHANNEL_RELIABLE, 0),
+            Reliability::MaxRetransmits(n) => (CHANNEL_PARTIAL_RELIABLE_REXMIT, n),
+            Reliability::MaxPacketLifeTime(ms) => (CHANNEL_PARTIAL_RELIABLE_TIMED, ms),
+        };
+        let channel_type = if self.ordered { channel_type } else { channel_type | CHANNEL_UNORDERED };
+        let label = self.label.as_bytes();
+        let protocol = self.protocol.as_bytes();
+        let mut msg = Vec::with_capacity(OPEN_HEADER_LEN + label.len() + protocol.len());
+        msg.push(DATA_CHANNEL_OPEN);
+        msg.push(channel_type);
+        msg.extend_from_slice(&self.priority.to_be_bytes());
+        msg.extend_from_slice(&param.to_be_bytes());
+        msg.extend_from_slice(&(label.len() as u16).to_be_bytes());
+        msg.extend_from_slice(&(protocol.len() as u16).to_be_bytes());
+        msg.extend_from_slice(label);
+        msg.extend_from_slice(protocol);
+        msg
+    }
+
+    // Decode a DATA_CHANNEL_OPEN message
+    pub(crate) fn decode_open(msg: &[u8]) -> Option<ChannelConfig> {
+        if msg.len() < OPEN_HEADER_LEN || msg[0] != DATA_CHANNEL_OPEN {
+            return None;
+        }
+        let channel_type = msg[1];
+        let priority = u16::from_be_bytes([msg[2], msg[3]]);
+        let param = u32::from_be_bytes([msg[4], msg[5], msg[6], msg[7]]);
+        let label_len = u16::from_be_bytes([msg[8], msg[9]]) as usize;
+        let protocol_len = u16::from_be_bytes([msg[10], msg[11]]) as usize;
+        if msg.len() < OPEN_HEADER_LEN + label_len + protocol_len {
+            return None;
+        }
+        let label = &msg[OPEN_HEADER_LEN..OPEN_HEADER_LEN + label_len];
+        let protocol = &msg[OPEN_HEADER_LEN + label_len..OPEN_HEADER_LEN + label_len + protocol_len];
+        let reliability = match channel_type & !CHANNEL_UNORDERED {
+            CHANNEL_RELIABLE => Reliability::Reliable,
+            CHANNEL_PARTIAL_RELIABLE_REXMIT => Reliability::MaxRetransmits(param),
+            CHANNEL_PARTIAL_RELIABLE_TIMED => Reliability::MaxPacketLifeTime(param),
+            _ => return None,
+        };
+        Some(ChannelConfig {
+            label: String::from_utf8_lossy(label).into_owned(),
+            protocol: String::from_utf8_lossy(protocol).into_owned(),
+            ordered: channel_type & CHANNEL_UNORDERED == 0,
+            reliability: reliability,
+            priority: priority,
+        })
+    }
+}
+
+/// Our side of the DTLS connection beneath SCTP, which decides the stream ids we
+/// may open channels on: even ones for the client, odd ones for the server.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DtlsRole {
+    Client,
+    Server,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChannelState {
+    /// We opened the channel, and the peer has not acknowledged it yet
+    Connecting,
+    Open,
+    /// We are closing the channel, and the peer has not closed its side yet
+    Closing,
+}
+
+struct Channel {
+    config: ChannelConfig,
+    state: ChannelState,
+}
+
+/// Something that happened on the data channels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataChannelEvent {
+    /// The peer opened a channel
+    Opened(u16),
+    /// The peer acknowledged a channel we opened
+    Acked(u16),
+    /// A message arrived on a channel.  `text` tells a string (UTF-8) from
+    /// binary data.
+    Message { sid: u16, data: Vec<u8>, text: bool },
+    /// A channel was closed (by either side) and its stream id may be reused
+    Closed(u16),
+}
+
+/// WebRTC data channels (RFC 8831) over an SCTP association, with the Data
+/// Channel Establishment Protocol (RFC 8832).
+///
+/// Each channel is a stream of the association.  Channels are opened in-band
+/// with DATA_CHANNEL_OPEN, and closed by resetting their streams.  The
+/// association normally runs over DTLS, through an AF_CONN transport such as an
+/// `Endpoint`, on a connected one-to-one `Socket<Conn>`.
+pub struct DataChannels<'a> {
+    socket: Socket<'a, Conn>,
+    role: DtlsRole,
+    channels: HashMap<u16, Channel>,
+    assembler: MessageAssembler,
+    events: VecDeque<DataChannelEvent>,
+    buf: Vec<u8>,
+}
+
+impl<'a> DataChannels<'a> {
+    /// Run data channels over `socket`, a one-to-one socket.  This turns on
+    /// `RecvRcvInfo`, stream reset, and stream reset events.
+    pub fn new(mut socket: Socket<'a, Conn>, role: DtlsRole) -> Result<DataChannels<'a>, Errno> {
+        socket.setsockopt(RecvRcvInfo(1))?;
+        socket.enable_stream_reset(SCTP_FUTURE_ASSOC, true)?;
+        socket.subscribe(SCTP_FUTURE_ASSOC, EventType::StreamReset, true)?;
+        Ok(DataChannels {
+            socket: socket,
+            role: role,
+            channels: HashMap::new(),
+            assembler: MessageAssembler::new(DEFAULT_MAX_MESSAGE_SIZE),
+            events: VecDeque::new(),
+            buf: vec![0; RECV_CHUNK],
+        })
+    }
+
+    /// Refuse received messages larger than `max_size` bytes
+    pub fn set_max_message_size(&mut self, max_size: usize) {
+        self.assembler = MessageAssembler::new(max_size);
+    }
+
+    pub fn get_ref(&self) -> &Socket<'a, Conn> {
+        &self.socket
+    }
+
+    pub fn get_mut(&mut self) -> &mut Socket<'a, Conn> {
+        &mut self.socket
+    }
+
+    /// The configuration and state of a channel
+    pub fn channel(&self, sid: u16) -> Option<(&ChannelConfig, ChannelState)> {
+        self.channels.get(&sid).map(|c| (&c.config, c.state))
+    }
+
+    /// Open a channel.  Returns its stream id.  Messages may be sent on it right
+    /// away, although they are sent ordered until the peer acknowledges it.
+    pub fn open(&mut self, config: ChannelConfig) -> Result<u16, Errno> {
+        let sid = self.allocate_sid()?;
+        let msg = config.encode_open();
+        self.send_dcep(sid, &msg)?;
+        self.channels.insert(sid, Channel {
+            config: config,
+            state: ChannelState::Connecting,
+        });
+        Ok(sid)
+    }
+
+    /// Send a message on a channel, as text (which should be UTF-8) or binary
+    pub fn send(&mut self, sid: u16, data: &[u8], text: bool) -> Result<usize, Errno> {
+        let (pr_info, unordered) = match self.channels.get(&sid) {
+            Some(channel) if channel.state != ChannelState::Closing => {
+                (channel.config.reliability.pr_info(),
+                 !channel.config.ordered && channel.state == ChannelState::Open)
+            },
+            _ => return Err(Errno(libc::EBADF)),
+        };
+        let (data, ppid) = match (data.is_empty(), text) {
+            (false, true) => (data, PPID_STRING),
+            (false, false) => (data, PPID_BINARY),
+            (true, true) => (&[0u8][..], PPID_STRING_EMPTY),
+            (true, false) => (&[0u8][..], PPID_BINARY_EMPTY),
+        };
+        // PPIDs travel in network byte order, and SCTP leaves them alone
+        let snd_info = SndInfo {
+            sid: sid,
+            ppid: htonl(ppid),
+            flags: if unordered { SctpFlags::UNORDERED } else { SctpFlags::empty() },
+            ..Default::default()
+        };
+        self.socket.sendv(data, None, Some(snd_info), pr_info, None, MsgFlags::empty())
+            .map(|len| if ppid == PPID_STRING_EMPTY || ppid == PPID_BINARY_EMPTY { 0 } else { len })
+    }
+
+    /// Close a channel, by resetting its outgoing stream.  The peer resets its
+    /// side in return, at which point `Closed` is reported.
+    pub fn close(&mut self, sid: u16) -> Result<(), Errno> {
+        match self.channels.get_mut(&sid) {
+            Some(channel) if channel.state != ChannelState::Closing => {
+                channel.state = ChannelState::Closing;
+            },
+            _ => return Err(Errno(libc::EBADF)),
+        }
+        self.socket.reset_streams(0, &[sid], StreamResetFlags::OUTGOING)
+    }
+
+    /// Receive until something happens.  Returns None once the association has
+    /// ended.  On a non-blocking socket, fails with EWOULDBLOCK when there is
+    /// nothing more to receive.
+    pub fn recv(&mut self) -> Result<Option<DataChannelEvent>, Errno> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(Some(event));
+            }
+            let (len, _from, rcv_info, flags) = self.socket.recvv(&mut self.buf)?;
+            if len == 0 && !flags.contains(MsgFlags::NOTIFICATION) {
+                return Ok(None);
+            }
+            let msg = match self.assembler.push(&self.buf[..len], rcv_info, flags) {
+                Ok(Some(msg)) => msg,
+                Ok(None) => continue,
+                Err(ref e) if e.0 == libc::EMSGSIZE => continue, // dropped
+                Err(e) => return Err(e),
+            };
+            self.handle(msg)?;
+        }
+    }
+
+    /// Handle a message received some other way than `recv` (e.g. on a socket
+    /// in callback mode).  Its events are then returned by `poll_event`.
+    pub fn handle(&mut self, msg: Message) -> Result<(), Errno> {
+        if msg.flags.contains(MsgFlags::NOTIFICATION) {
+            if let Some(Notification::StreamReset(ev)) = Notification::parse(&msg.data) {
+                if ev.flags.contains(StreamResetFlags::INCOMING)
+                    && !ev.flags.intersects(StreamResetFlags::DENIED | StreamResetFlags::FAILED)
+                {
+                    // An empty list means every stream was reset
+                    let sids = if ev.streams.is_empty() {
+                        let mut all: Vec<u16> = self.channels.keys().cloned().collect();
+                        all.sort();
+                        all
+                    } else {
+                        ev.streams
+                    };
+                    for sid in sids {
+                        self.peer_closed(sid)?;
+                    }
+                }
+            }
+            return Ok(());
+        }
+        let sid = msg.sid;
+        match ntohl(msg.ppid) {
+            PPID_DCEP => self.handle_dcep(sid, &msg.data),
+            ppid => {
+                let text = match ppid {
+                    PPID_STRING | PPID_STRING_PARTIAL | PPID_STRING_EMPTY => true,
+                    PPID_BINARY | PPID_BINARY_PARTIAL | PPID_BINARY_EMPTY => false,
+                    _ => return Ok(()), // not for us
+                };
+                let empty = ppid == PPID_STRING_EMPTY || ppid == PPID_BINARY_EMPTY;
+                match self.channels.get_mut(&sid) {
+                    // Data on a channel we opened implies the peer acknowledged it
+                    Some(channel) if channel.state == ChannelState::Connecting => {
+                        channel.state = ChannelState::Open;
+                    },
+                    Some(_) => (),
+                    None => return Ok(()),
+                }
+                self.events.push_back(DataChannelEvent::Message {
+                    sid: sid,
+                    data: if empty { Vec::new() } else { msg.data },
+                    text: text,
+                });
+                Ok(())
+            },
+        }
+    }
+
+    /// The next event from messages passed to `handle`
+    pub fn poll_event(&mut self) -> Option<DataChannelEvent> {
+        self.events.pop_front()
+    }
+
+    fn handle_dcep(&mut self, sid: u16, data: &[u8]) -> Result<(), Errno> {
+        match data.first() {
+            Some(&DATA_CHANNEL_OPEN) => {
+                let config = match ChannelConfig::decode_open(data) {
+                    Some(config) => config,
+                    None => return Ok(()), // malformed
+                };
+                if self.channels.contains_key(&sid) {
+                    return Ok(()); // the stream is taken
+                }
+                if self.our_sid(sid) {
+                    // The peer may only open channels on stream ids of its own
+                    // parity.  Resetting the stream could disturb one we are
+                    // opening ourselves, so just ignore it.
+                    return Ok(());
+                }
+                self.send_dcep(sid, &[DATA_CHANNEL_ACK])?;
+                self.channels.insert(sid, Channel {
+                    config: config,
+                    state: ChannelState::Open,
+                });
+                self.events.push_back(DataChannelEvent::Opened(sid));
+            },
+            Some(&DATA_CHANNEL_ACK) => {
+                if let Some(channel) = self.channels.get_mut(&sid) {
+                    if channel.state == ChannelState::Connecting {
+                        channel.state = ChannelState::Open;
+                        self.events.push_back(DataChannelEvent::Acked(sid));
+                    }
+                }
+            },
+            _ => (),
+        }
+        Ok(())
+    }
+
+    // The peer reset its outgoing stream `sid`
+    fn peer_closed(&mut self, sid: u16) -> Result<(), Errno> {
+        let state = match self.channels.remove(&sid) {
+            Some(channel) => channel.state,
+            None => return Ok(()),
+        };
+        self.events.push_back(DataChannelEvent::Closed(sid));
+        if state != ChannelState::Closing {
+            // The peer closed the channel; close our side too
+            self.socket.reset_streams(0, &[sid], StreamResetFlags::OUTGOING)?;
+        }
+        Ok(())
+    }
+
+    fn send_dcep(&mut self, sid: u16, msg: &[u8]) -> Result<(), Errno> {
+        let snd_info = SndInfo {
+            sid: sid,
+            ppid: htonl(PPID_DCEP),
+            ..Default::default()
+        };
+        self.socket.sendv(msg, None, Some(snd_info), None, None, MsgFlags::empty())
+            .map(|_| ())
+    }
+
+    // Whether `sid` is of our parity, for channels we open
+    fn our_sid(&self, sid: u16) -> bool {
+        match self.role {
+            DtlsRole::Client => sid % 2 == 0,
+            DtlsRole::Server => sid % 2 == 1,
+        }
+    }
+
+    // The lowest free stream id of our parity, among the outgoing streams the
+    // association has
+    fn allocate_sid(&mut self) -> Result<u16, Errno> {
+        let first = match self.role {
+            DtlsRole::Client => 0,
+            DtlsRole::Server => 1,
+        };
+        let streams = self.socket.getsockopt(Status(unsafe { mem::zeroed() }))?.0.sstat_outstrms;
+        (first..streams).step_by(2)
+            .find(|sid| !self.channels.contains_key(sid))
+            .ok_or(Errno(libc::ENOSPC))
+    }
+}
+
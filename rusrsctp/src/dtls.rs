@@ -0,0 +1,325 @@
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::UdpSocket;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use errno::Errno;
+use libc;
+use openssl::asn1::Asn1Time;
+use openssl::bn::{BigNum, MsbOption};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::ssl::{ErrorCode, HandshakeError, Ssl, SslContext, SslMethod, SslStream,
+                   SslVerifyMode};
+use openssl::x509::{X509, X509NameBuilder, X509Ref};
+use super::{UsrSctp, ConnAddr, DtlsRole, conn_input};
+
+// How long the reader waits for a datagram before checking whether to stop
+const READ_TIMEOUT: Duration = Duration::from_millis(10);
+// Larger than any datagram we could be sent
+const MAX_DATAGRAM: usize = 65536;
+
+/// Errors from setting up a DTLS transport
+#[derive(Debug)]
+pub enum DtlsError {
+    /// A socket operation failed
+    Io(Errno),
+    /// OpenSSL failed
+    Ssl(ErrorStack),
+    /// The DTLS handshake failed
+    Handshake(String),
+    /// The peer's certificate did not pass verification
+    Verification,
+}
+
+impl fmt::Display for DtlsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DtlsError::Io(ref e) => write!(f, "I/O error: {}", e),
+            DtlsError::Ssl(ref e) => write!(f, "OpenSSL error: {}", e),
+            DtlsError::Handshake(ref e) => write!(f, "DTLS handshake failed: {}", e),
+            DtlsError::Verification => write!(f, "peer certificate rejected"),
+        }
+    }
+}
+
+impl ::std::error::Error for DtlsError {}
+
+impl From<ErrorStack> for DtlsError {
+    fn from(e: ErrorStack) -> DtlsError {
+        DtlsError::Ssl(e)
+    }
+}
+
+fn io_to_errno(e: io::Error) -> Errno {
+    Errno(e.raw_os_error().unwrap_or(libc::EIO))
+}
+
+/// The SHA-256 fingerprint of a certificate, as exchanged in WebRTC signalling
+pub fn fingerprint(cert: &X509Ref) -> Result<Vec<u8>, ErrorStack> {
+    cert.digest(MessageDigest::sha256()).map(|d| d.to_vec())
+}
+
+type VerifyFn = dyn Fn(&X509Ref) -> bool + Send + Sync;
+
+/// Our certificate, and how to check the peer's.
+///
+/// As in WebRTC, certificates are usually self-signed, and are authenticated by
+/// comparing their fingerprints with ones exchanged some other way, rather than
+/// through a certificate authority.  Unless a check is configured, any peer
+/// certificate is accepted.
+pub struct DtlsConfig {
+    certificate: X509,
+    key: PKey<Private>,
+    verify: Option<Box<VerifyFn>>,
+}
+
+impl DtlsConfig {
+    pub fn new(certificate: X509, key: PKey<Private>) -> DtlsConfig {
+        DtlsConfig {
+            certificate: certificate,
+            key: key,
+            verify: None,
+        }
+    }
+
+    /// Generate a self-signed ECDSA (P-256) certificate, valid for 30 days
+    pub fn self_signed(common_name: &str) -> Result<DtlsConfig, ErrorStack> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        let key = PKey::from_ec_key(EcKey::generate(&group)?)?;
+        let mut name = X509NameBuilder::new()?;
+        name.append_entry_by_nid(Nid::COMMONNAME, common_name)?;
+        let name = name.build();
+        let mut serial = BigNum::new()?;
+        serial.rand(64, MsbOption::MAYBE_ZERO, false)?;
+        let mut builder = X509::builder()?;
+        builder.set_version(2)?;
+        builder.set_serial_number(&serial.to_asn1_integer()?)?;
+        builder.set_subject_name(&name)?;
+        builder.set_issuer_name(&name)?;
+        builder.set_pubkey(&key)?;
+        builder.set_not_before(&Asn1Time::days_from_now(0)?)?;
+        builder.set_not_after(&Asn1Time::days_from_now(30)?)?;
+        builder.sign(&key, MessageDigest::sha256())?;
+        Ok(DtlsConfig::new(builder.build(), key))
+    }
+
+    /// Only accept a peer whose certificate has this SHA-256 fingerprint
+    pub fn verify_fingerprint(self, expected: Vec<u8>) -> DtlsConfig {
+        self.verify_with(move |cert| {
+            fingerprint(cert).map(|f| f == expected).unwrap_or(false)
+        })
+    }
+
+    /// Only accept a peer whose certificate passes `verify`
+    pub fn verify_with<F>(mut self, verify: F) -> DtlsConfig
+        where F: Fn(&X509Ref) -> bool + Send + Sync + 'static
+    {
+        self.verify = Some(Box::new(verify));
+        self
+    }
+
+    pub fn certificate(&self) -> &X509Ref {
+        &self.certificate
+    }
+
+    /// The SHA-256 fingerprint of our certificate, to tell the peer
+    pub fn fingerprint(&self) -> Result<Vec<u8>, ErrorStack> {
+        fingerprint(&self.certificate)
+    }
+
+    fn ssl(&self) -> Result<Ssl, ErrorStack> {
+        let mut ctx = SslContext::builder(SslMethod::dtls())?;
+        ctx.set_certificate(&self.certificate)?;
+        ctx.set_private_key(&self.key)?;
+        ctx.check_private_key()?;
+        // Self-signed certificates do not verify; they are checked after the handshake
+        ctx.set_verify_callback(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT,
+                                |_preverified, _ctx| true);
+        Ssl::new(&ctx.build())
+    }
+}
+
+// A connected UDP socket as the byte stream OpenSSL wants, one datagram per
+// read or write.  During the handshake reads come straight from the socket.
+// Afterwards our reader thread receives, without holding the SslStream lock (so
+// that usrsctp's threads can send meanwhile), and queues datagrams here for
+// OpenSSL to decrypt.
+struct Datagrams {
+    udp: UdpSocket,
+    inbox: Option<VecDeque<Vec<u8>>>,
+}
+
+impl Read for Datagrams {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.inbox {
+            None => self.udp.recv(buf),
+            Some(ref mut inbox) => match inbox.pop_front() {
+                Some(datagram) => {
+                    let len = datagram.len().min(buf.len());
+                    buf[..len].copy_from_slice(&datagram[..len]);
+                    Ok(len)
+                },
+                None => Err(io::ErrorKind::WouldBlock.into()),
+            },
+        }
+    }
+}
+
+impl Write for Datagrams {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.udp.send(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// SCTP over DTLS over UDP (RFC 8261): an AF_CONN link whose packets travel as
+/// DTLS application data.
+///
+/// Create sockets of type `Socket<Conn>`, and bind and connect them to `addr()`,
+/// as with an `Endpoint`.  A thread of our own receives from the UDP socket.
+///
+/// The handshake relies on the network not losing its packets, since DTLS
+/// retransmission timers are not driven; this suits loopback and tunnels over
+/// reliable networks better than the open Internet.
+pub struct DtlsTransport<'a> {
+    sctp: &'a UsrSctp,
+    addr: ConnAddr,
+    role: DtlsRole,
+    stop: Arc<AtomicBool>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl<'a> DtlsTransport<'a> {
+    /// Handshake as the DTLS client over `udp`, which must be connected to the peer
+    pub fn connect(sctp: &'a UsrSctp, udp: UdpSocket, config: &DtlsConfig)
+                   -> Result<DtlsTransport<'a>, DtlsError>
+    {
+        DtlsTransport::start(sctp, udp, config, DtlsRole::Client)
+    }
+
+    /// Handshake as the DTLS server over `udp`, which must be connected to the peer
+    pub fn accept(sctp: &'a UsrSctp, udp: UdpSocket, config: &DtlsConfig)
+                  -> Result<DtlsTransport<'a>, DtlsError>
+    {
+        DtlsTransport::start(sctp, udp, config, DtlsRole::Server)
+    }
+
+    fn start(sctp: &'a UsrSctp, udp: UdpSocket, config: &DtlsConfig, role: DtlsRole)
+             -> Result<DtlsTransport<'a>, DtlsError>
+    {
+        udp.set_read_timeout(Some(READ_TIMEOUT)).map_err(|e| DtlsError::Io(io_to_errno(e)))?;
+        let receiver = udp.try_clone().map_err(|e| DtlsError::Io(io_to_errno(e)))?;
+        let ssl = config.ssl()?;
+        let datagrams = Datagrams { udp: udp, inbox: None };
+        let mut result = match role {
+            DtlsRole::Client => ssl.connect(datagrams),
+            DtlsRole::Server => ssl.accept(datagrams),
+        };
+        let mut stream = loop {
+            match result {
+                Ok(stream) => break stream,
+                // the read timed out; keep waiting for the peer
+                Err(HandshakeError::WouldBlock(mid)) => result = mid.handshake(),
+                Err(HandshakeError::SetupFailure(e)) => return Err(DtlsError::Ssl(e)),
+                Err(HandshakeError::Failure(mid)) => {
+                    return Err(DtlsError::Handshake(mid.error().to_string()));
+                },
+            }
+        };
+        if let Some(ref verify) = config.verify {
+            match stream.ssl().peer_certificate() {
+                Some(ref cert) if verify(cert) => (),
+                _ => return Err(DtlsError::Verification),
+            }
+        }
+
+        stream.get_mut().inbox = Some(VecDeque::new());
+        let ssl = Arc::new(Mutex::new(stream));
+        let out = ssl.clone();
+        let addr = sctp.register_conn(move |packet| {
+            let mut stream = out.lock().unwrap_or_else(|e| e.into_inner());
+            let _ = stream.ssl_write(packet); // lost packets are SCTP's to recover
+        });
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let reader = thread::spawn(move || {
+            let mut buf = vec![0u8; MAX_DATAGRAM];
+            while !stopped.load(Ordering::SeqCst) {
+                // wait for a datagram without the lock, so writers are not held up
+                let len = match receiver.recv(&mut buf) {
+                    Ok(len) => len,
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut => continue,
+                    Err(_) => break,
+                };
+                // Decrypt under the lock, but hand the packets to SCTP after
+                // releasing it, as SCTP may well answer (through conn_output)
+                // straight away
+                let mut packets = Vec::new();
+                let mut closed = false;
+                {
+                    let mut stream = ssl.lock().unwrap_or_else(|e| e.into_inner());
+                    if let Some(ref mut inbox) = stream.get_mut().inbox {
+                        inbox.push_back(buf[..len].to_vec());
+                    }
+                    loop {
+                        match stream.ssl_read(&mut buf) {
+                            Ok(0) => { closed = true; break; },
+                            Ok(len) => packets.push(buf[..len].to_vec()),
+                            Err(ref e) if e.code() == ErrorCode::WANT_READ => break,
+                            Err(ref e) if e.code() == ErrorCode::ZERO_RETURN => {
+                                closed = true;
+                                break;
+                            },
+                            Err(_) => break, // e.g. a stray datagram; drop it
+                        }
+                    }
+                }
+                for packet in packets {
+                    conn_input(addr, &packet);
+                }
+                if closed {
+                    break;
+                }
+            }
+        });
+        Ok(DtlsTransport {
+            sctp: sctp,
+            addr: addr,
+            role: role,
+            stop: stop,
+            reader: Some(reader),
+        })
+    }
+
+    /// The address of this link, to bind and connect sockets to
+    pub fn addr(&self) -> ConnAddr {
+        self.addr
+    }
+
+    /// Our side of the handshake (which picks data channel stream ids)
+    pub fn role(&self) -> DtlsRole {
+        self.role
+    }
+}
+
+impl<'a> Drop for DtlsTransport<'a> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+        self.sctp.deregister_conn(self.addr);
+    }
+}
@@ -0,0 +1,22 @@
+use std::net::SocketAddr;
+use errno::Errno;
+use super::{Socket, Mode, OneToOne, DualStack, RcvInfo, MsgFlags};
+
+/// Peer addresses of dual-stack sockets as `SocketAddr`s, IPv4 ones unmapped
+impl<'a, M: Mode> Socket<'a, DualStack, M> {
+    /// Receive data, as `recvv` does
+    pub fn recv_from(&mut self, data: &mut [u8])
+                     -> Result<(usize, Option<SocketAddr>, Option<RcvInfo>, MsgFlags), Errno>
+    {
+        let (len, from, rcv_info, flags) = self.recvv(data)?;
+        Ok((len, from.map(SocketAddr::from), rcv_info, flags))
+    }
+}
+
+impl<'a> Socket<'a, DualStack, OneToOne> {
+    /// Accept an association, as `accept` does
+    pub fn accept_from(&mut self) -> Result<(SocketAddr, Socket<'a, DualStack>), Errno> {
+        let (addr, port, socket) = self.accept()?;
+        Ok((SocketAddr::new(addr, port), socket))
+    }
+}
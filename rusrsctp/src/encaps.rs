@@ -0,0 +1,59 @@
+
+use std::mem;
+use std::net::SocketAddr;
+use errno::Errno;
+use rusrsctp_sys::*;
+use super::{UsrSctp, Socket, Ip, Mode, AssocId, RemoteUdpEncapsPort, htons, ntohs,
+            socket_addr_to_storage};
+
+/// The UDP encapsulation port (RFC 6951).
+///
+/// `UsrSctp::new(Some(port), ..)` picks the local port SCTP is tunnelled over.
+/// usrsctp opens its UDP sockets on that port once, as it starts, so the port
+/// is fixed from then on; to use another port, start SCTP with it.
+impl UsrSctp {
+    /// The local UDP port SCTP is tunnelled over (0 if none)
+    pub fn get_udp_encaps_port(&self) -> u16 {
+        unsafe {
+            usrsctp_sysctl_get_sctp_udp_tunneling_port() as u16
+        }
+    }
+}
+
+/// The remote UDP encapsulation port, which is the UDP port SCTP packets are sent
+/// to.  Set it on an association (or SCTP_FUTURE_ASSOC) as a whole, or for a
+/// single peer address of one.  A port of 0 sends SCTP over IP directly.
+impl<'a, T: 'a + Ip, M: Mode> Socket<'a, T, M> {
+    pub fn set_remote_encaps_port(&mut self, assoc_id: AssocId, peer: Option<SocketAddr>,
+                                  port: u16) -> Result<(), Errno>
+    {
+        self.setsockopt(RemoteUdpEncapsPort(sctp_udpencaps {
+            sue_address: encaps_address::<T>(peer),
+            sue_assoc_id: assoc_id,
+            sue_port: htons(port),
+        }))
+    }
+
+    pub fn get_remote_encaps_port(&mut self, assoc_id: AssocId, peer: Option<SocketAddr>)
+                                  -> Result<u16, Errno>
+    {
+        let opt = self.getsockopt(RemoteUdpEncapsPort(sctp_udpencaps {
+            sue_address: encaps_address::<T>(peer),
+            sue_assoc_id: assoc_id,
+            sue_port: 0,
+        }))?;
+        Ok(ntohs(opt.0.sue_port))
+    }
+}
+
+// The peer address, or the wildcard address of the socket's family for none
+fn encaps_address<T: Ip>(peer: Option<SocketAddr>) -> sockaddr_storage {
+    match peer {
+        Some(ref addr) => socket_addr_to_storage(addr),
+        None => {
+            let mut ss: sockaddr_storage = unsafe { mem::zeroed() };
+            ss.ss_family = T::pf() as sa_family_t;
+            ss
+        },
+    }
+}
@@ -0,0 +1,95 @@
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use errno::Errno;
+use rusrsctp_sys::*;
+use super::{UsrSctp, Socket, Mode, Conn, ConnAddr, NO_THREADS};
+
+/// A sans-IO facade over one AF_CONN link, for embedding SCTP into an
+/// application's own event loop (and, typically, under its own DTLS).
+///
+/// The application owns all I/O: it feeds datagrams received from the peer to
+/// `handle_input`, and sends whatever `poll_transmit` hands out.  With SCTP
+/// started by `UsrSctp::new_nothreads` it owns the timing too: it should call
+/// `handle_timeout` once the deadline from `poll_timeout` passes (and may call
+/// it more often).  Data is sent and received with the usual `Socket` API, on
+/// sockets from `socket`.
+///
+/// Both ends of a link bind to and connect to the endpoint's address; the
+/// associations are told apart by port.
+pub struct Endpoint<'a> {
+    sctp: &'a UsrSctp,
+    addr: ConnAddr,
+    transmits: Arc<Mutex<VecDeque<Vec<u8>>>>,
+}
+
+impl<'a> Endpoint<'a> {
+    pub fn new(sctp: &'a UsrSctp) -> Endpoint<'a> {
+        let transmits = Arc::new(Mutex::new(VecDeque::new()));
+        let queue = transmits.clone();
+        let addr = sctp.register_conn(move |packet| {
+            queue.lock().unwrap_or_else(|e| e.into_inner()).push_back(packet.to_vec());
+        });
+        Endpoint {
+            sctp: sctp,
+            addr: addr,
+            transmits: transmits,
+        }
+    }
+
+    /// The address of this endpoint's link, to bind and connect sockets to
+    pub fn addr(&self) -> ConnAddr {
+        self.addr
+    }
+
+    /// Create a socket for use over this endpoint
+    pub fn socket<M: Mode>(&self) -> Result<Socket<'a, Conn, M>, Errno> {
+        self.sctp.socket::<Conn, M>()
+    }
+
+    /// Process a datagram received from the peer
+    pub fn handle_input(&mut self, datagram: &[u8]) {
+        self.sctp.conn_input(self.addr, datagram);
+    }
+
+    /// The next datagram to send to the peer, if any
+    pub fn poll_transmit(&mut self) -> Option<Vec<u8>> {
+        self.transmits.lock().unwrap_or_else(|e| e.into_inner()).pop_front()
+    }
+
+    /// When `handle_timeout` should next be called: when SCTP's next timer
+    /// expires, or right away if SCTP's clock has not been started yet.  None if
+    /// no timer is running, or if SCTP keeps its own time (i.e. was not started
+    /// with `new_nothreads`).
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        if !NO_THREADS.load(Ordering::SeqCst) {
+            return None;
+        }
+        let now = match self.sctp.now() {
+            Some(now) => now,
+            None => return Some(Instant::now()),
+        };
+        // Milliseconds until the next timer expires, or -1 if there is none
+        let millis = unsafe { usrsctp_get_timeout() };
+        if millis < 0 {
+            None
+        } else {
+            Some(now + Duration::from_millis(millis as u64))
+        }
+    }
+
+    /// Run any timers due by `now`.  Does nothing if SCTP keeps its own time.
+    pub fn handle_timeout(&mut self, now: Instant) {
+        if NO_THREADS.load(Ordering::SeqCst) {
+            self.sctp.advance_to(now);
+        }
+    }
+}
+
+impl<'a> Drop for Endpoint<'a> {
+    fn drop(&mut self) {
+        self.sctp.deregister_conn(self.addr);
+    }
+}
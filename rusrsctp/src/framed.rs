@@ -0,0 +1,245 @@
+
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use errno::Errno;
+use libc;
+use super::{UsrSctp, ConnAddr, conn_input};
+
+// Each SCTP packet is sent as a two byte big-endian length followed by the packet
+fn frame(packet: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(2 + packet.len());
+    framed.extend_from_slice(&(packet.len() as u16).to_be_bytes());
+    framed.extend_from_slice(packet);
+    framed
+}
+
+/// SCTP over a byte stream, such as a TCP connection through a proxy which
+/// passes nothing else: an AF_CONN link whose packets are sent with a two byte
+/// length prefix.
+///
+/// Multi-streaming and partial reliability still work at the SCTP layer,
+/// although the byte stream beneath delivers everything, in order.  Create
+/// sockets of type `Socket<Conn>`, and bind and connect them to `addr()`, as
+/// with an `Endpoint`.  A thread of our own reads from the stream.
+pub struct FramedTransport<'a> {
+    sctp: &'a UsrSctp,
+    addr: ConnAddr,
+    // Cleared (under the lock) once packets must no longer be passed to SCTP
+    alive: Arc<Mutex<bool>>,
+    reader: Option<JoinHandle<()>>,
+    tcp: Option<TcpStream>,
+}
+
+impl<'a> FramedTransport<'a> {
+    /// Run over a TCP connection
+    pub fn tcp(sctp: &'a UsrSctp, stream: TcpStream) -> Result<FramedTransport<'a>, Errno> {
+        let io_to_errno = |e: ::std::io::Error| Errno(e.raw_os_error().unwrap_or(libc::EIO));
+        stream.set_nodelay(true).map_err(io_to_errno)?;
+        let reader = stream.try_clone().map_err(io_to_errno)?;
+        let writer = stream.try_clone().map_err(io_to_errno)?;
+        let mut transport = FramedTransport::new(sctp, reader, writer);
+        transport.tcp = Some(stream);
+        Ok(transport)
+    }
+
+    /// Run over any byte stream, given its two halves.  Since a blocked read can
+    /// not be interrupted in general, the reading thread lingers after the
+    /// transport is dropped, until the stream ends (`tcp` shuts its connection
+    /// down instead).
+    pub fn new<R, W>(sctp: &'a UsrSctp, mut reader: R, mut writer: W) -> FramedTransport<'a>
+        where R: Read + Send + 'static,
+              W: Write + Send + 'static
+    {
+        let addr = sctp.register_conn(move |packet| {
+            let _ = writer.write_all(&frame(packet)).and_then(|_| writer.flush());
+        });
+        let alive = Arc::new(Mutex::new(true));
+        let still_alive = alive.clone();
+        let reader = thread::spawn(move || {
+            let mut buf = vec![0u8; 65535];
+            loop {
+                let mut len = [0u8; 2];
+                if reader.read_exact(&mut len).is_err() {
+                    break;
+                }
+                let len = u16::from_be_bytes(len) as usize;
+                if reader.read_exact(&mut buf[..len]).is_err() {
+                    break;
+                }
+                let alive = still_alive.lock().unwrap_or_else(|e| e.into_inner());
+                if !*alive {
+                    break;
+                }
+                conn_input(addr, &buf[..len]);
+            }
+        });
+        FramedTransport {
+            sctp: sctp,
+            addr: addr,
+            alive: alive,
+            reader: Some(reader),
+            tcp: None,
+        }
+    }
+
+    /// The address of this link, to bind and connect sockets to
+    pub fn addr(&self) -> ConnAddr {
+        self.addr
+    }
+}
+
+impl<'a> Drop for FramedTransport<'a> {
+    fn drop(&mut self) {
+        *self.alive.lock().unwrap_or_else(|e| e.into_inner()) = false;
+        if let Some(ref tcp) = self.tcp {
+            let _ = tcp.shutdown(Shutdown::Both);
+            if let Some(reader) = self.reader.take() {
+                let _ = reader.join();
+            }
+        }
+        self.sctp.deregister_conn(self.addr);
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod async_framed {
+    use std::collections::VecDeque;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Waker};
+    use errno::Errno;
+    use libc;
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+    use super::frame;
+    use super::super::{UsrSctp, ConnAddr, conn_input};
+
+    // Packets from SCTP waiting to be written, and the task to wake for them
+    struct Outgoing {
+        packets: Mutex<VecDeque<Vec<u8>>>,
+        waker: Mutex<Option<Waker>>,
+    }
+
+    /// Like `FramedTransport`, but over a tokio `AsyncRead + AsyncWrite` byte
+    /// stream, carried by the future returned from `run`.
+    pub struct AsyncFramedTransport<'a> {
+        sctp: &'a UsrSctp,
+        addr: ConnAddr,
+        outgoing: Arc<Outgoing>,
+    }
+
+    impl<'a> AsyncFramedTransport<'a> {
+        pub fn new(sctp: &'a UsrSctp) -> AsyncFramedTransport<'a> {
+            let outgoing = Arc::new(Outgoing {
+                packets: Mutex::new(VecDeque::new()),
+                waker: Mutex::new(None),
+            });
+            let out = outgoing.clone();
+            let addr = sctp.register_conn(move |packet| {
+                out.packets.lock().unwrap_or_else(|e| e.into_inner()).push_back(frame(packet));
+                if let Some(waker) = out.waker.lock().unwrap_or_else(|e| e.into_inner()).take() {
+                    waker.wake();
+                }
+            });
+            AsyncFramedTransport {
+                sctp: sctp,
+                addr: addr,
+                outgoing: outgoing,
+            }
+        }
+
+        /// The address of this link, to bind and connect sockets to
+        pub fn addr(&self) -> ConnAddr {
+            self.addr
+        }
+
+        /// Carry packets over `stream`.  The future resolves when the stream ends.
+        pub fn run<'s, S>(&'s self, stream: S) -> Run<'s, 'a, S>
+            where S: AsyncRead + AsyncWrite + Unpin
+        {
+            Run {
+                transport: self,
+                stream: stream,
+                inbuf: Vec::new(),
+                outbuf: Vec::new(),
+                written: 0,
+            }
+        }
+    }
+
+    impl<'a> Drop for AsyncFramedTransport<'a> {
+        fn drop(&mut self) {
+            self.sctp.deregister_conn(self.addr);
+        }
+    }
+
+    /// Future returned by `AsyncFramedTransport::run`
+    pub struct Run<'s, 'a: 's, S> {
+        transport: &'s AsyncFramedTransport<'a>,
+        stream: S,
+        // Received bytes not yet making up a whole packet
+        inbuf: Vec<u8>,
+        // The framed packet being written, and how much of it is written
+        outbuf: Vec<u8>,
+        written: usize,
+    }
+
+    impl<'s, 'a: 's, S: AsyncRead + AsyncWrite + Unpin> Future for Run<'s, 'a, S> {
+        type Output = Result<(), Errno>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+            let this = self.get_mut();
+            let io_to_errno = |e: ::std::io::Error| Errno(e.raw_os_error().unwrap_or(libc::EIO));
+            // Register before looking at the queue, so that no packet is missed
+            *this.transport.outgoing.waker.lock().unwrap_or_else(|e| e.into_inner()) =
+                Some(cx.waker().clone());
+
+            let mut chunk = [0u8; 4096];
+            loop {
+                let mut buf = ReadBuf::new(&mut chunk);
+                match Pin::new(&mut this.stream).poll_read(cx, &mut buf) {
+                    Poll::Ready(Ok(())) if buf.filled().is_empty() => return Poll::Ready(Ok(())),
+                    Poll::Ready(Ok(())) => this.inbuf.extend_from_slice(buf.filled()),
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(io_to_errno(e))),
+                    Poll::Pending => break,
+                }
+                while this.inbuf.len() >= 2 {
+                    let len = u16::from_be_bytes([this.inbuf[0], this.inbuf[1]]) as usize;
+                    if this.inbuf.len() < 2 + len {
+                        break;
+                    }
+                    conn_input(this.transport.addr, &this.inbuf[2..2 + len]);
+                    this.inbuf.drain(..2 + len);
+                }
+            }
+
+            loop {
+                if this.written == this.outbuf.len() {
+                    let next = this.transport.outgoing.packets.lock()
+                        .unwrap_or_else(|e| e.into_inner()).pop_front();
+                    match next {
+                        Some(framed) => {
+                            this.outbuf = framed;
+                            this.written = 0;
+                        },
+                        None => break,
+                    }
+                }
+                match Pin::new(&mut this.stream).poll_write(cx, &this.outbuf[this.written..]) {
+                    Poll::Ready(Ok(0)) => return Poll::Ready(Err(Errno(libc::EPIPE))),
+                    Poll::Ready(Ok(n)) => this.written += n,
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(io_to_errno(e))),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            match Pin::new(&mut this.stream).poll_flush(cx) {
+                Poll::Ready(Err(e)) => Poll::Ready(Err(io_to_errno(e))),
+                _ => Poll::Pending,
+            }
+        }
+    }
+}
+#[cfg(feature = "tokio")]
+pub use self::async_framed::*;
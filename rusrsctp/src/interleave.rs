@@ -0,0 +1,39 @@
+
+use errno::Errno;
+use rusrsctp_sys::*;
+use super::{Socket, Ip, Mode, AssocId, FragmentInterleave, InterleavingSupported, RecvRcvInfo};
+
+/// Message interleaving (I-DATA, RFC 8260).
+///
+/// Without I-DATA, a large message occupies the association until it is fully
+/// sent, so small messages on other streams wait behind it.  With I-DATA,
+/// fragments of messages on different streams are interleaved.
+///
+/// On receive, `recvv` then returns partial deliveries of different streams
+/// interleaved with each other.  Each one comes with an `RcvInfo` giving its
+/// `sid` (and the lower 16 bits of its message identifier in `ssn`); the last
+/// part of each message has `MsgFlags::EOR` set.
+impl<'a, T: 'a + Ip, M: Mode> Socket<'a, T, M> {
+    /// Offer I-DATA to peers of associations created afterwards.  This sets the
+    /// fragment interleave level to 2 (which I-DATA requires) and turns on
+    /// `RecvRcvInfo`, without which interleaved partial deliveries can not be told
+    /// apart.  Must be done before connecting or listening.
+    pub fn enable_interleaving(&mut self) -> Result<(), Errno> {
+        self.setsockopt(FragmentInterleave(2))?;
+        self.setsockopt(RecvRcvInfo(1))?;
+        self.setsockopt(InterleavingSupported(sctp_assoc_value {
+            assoc_id: SCTP_FUTURE_ASSOC,
+            assoc_value: 1,
+        }))
+    }
+
+    /// Whether I-DATA is in use on an association (i.e. we offered it, and the
+    /// peer supports it).  Use SCTP_FUTURE_ASSOC to check whether we offer it.
+    pub fn interleaving_negotiated(&mut self, assoc_id: AssocId) -> Result<bool, Errno> {
+        let opt = self.getsockopt(InterleavingSupported(sctp_assoc_value {
+            assoc_id: assoc_id,
+            assoc_value: 0,
+        }))?;
+        Ok(opt.0.assoc_value != 0)
+    }
+}
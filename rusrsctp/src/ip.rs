@@ -1,16 +1,37 @@
 
-use std::net::{Ipv4Addr, Ipv6Addr};
-use rusrsctp_sys::{sockaddr_in, sockaddr_in6, PF_INET, PF_INET6,
-                   AF_INET, AF_INET6, in_addr, in6_addr};
-use super::{htons, htonl};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::mem;
+use std::ptr;
+use std::os::raw::c_void;
+use rusrsctp_sys::{sockaddr_in, sockaddr_in6, sockaddr_conn, sockaddr_storage, PF_INET, PF_INET6,
+                   AF_INET, AF_INET6, AF_CONN, in_addr, in6_addr};
+use super::{htons, htonl, ntohs, ntohl};
 
 pub trait Ip {
-    type Addr;
+    type Addr: Copy;
     type Sockaddr;
     fn pf() -> i32;
     fn to_sockaddr(addr: Self::Addr, port: u16) -> Self::Sockaddr;
     fn to_sockaddr_wildcard() -> Self::Sockaddr;
     fn from_sockaddr(sockaddr: Self::Sockaddr) -> (Self::Addr, u16);
+    /// Whether sockets of this type take addresses of the family `af`
+    fn has_family(af: u32) -> bool {
+        af as i32 == Self::pf()
+    }
+    /// Whether sockets of this type are IPv6 sockets with IPV6_V6ONLY off
+    fn dual_stack() -> bool {
+        false
+    }
+    fn to_sockaddr_storage(addr: Self::Addr, port: u16) -> sockaddr_storage {
+        let sa = Self::to_sockaddr(addr, port);
+        unsafe {
+            let mut ss: sockaddr_storage = mem::zeroed();
+            ptr::copy_nonoverlapping(&sa as *const Self::Sockaddr as *const u8,
+                                     &mut ss as *mut sockaddr_storage as *mut u8,
+                                     mem::size_of::<Self::Sockaddr>());
+            ss
+        }
+    }
 }
 pub struct Ipv4;
 impl Ip for Ipv4 {
@@ -31,7 +52,7 @@ impl Ip for Ipv4 {
         Self::to_sockaddr(Ipv4Addr::UNSPECIFIED, 0)
     }
     fn from_sockaddr(sockaddr: Self::Sockaddr) -> (Self::Addr, u16) {
-        (sockaddr.sin_addr.s_addr.into(), sockaddr.sin_port)
+        (ntohl(sockaddr.sin_addr.s_addr).into(), ntohs(sockaddr.sin_port))
     }
 }
 pub struct Ipv6;
@@ -60,8 +81,137 @@ impl Ip for Ipv6 {
         Self::to_sockaddr(Ipv6Addr::UNSPECIFIED, 0)
     }
     fn from_sockaddr(sockaddr: Self::Sockaddr) -> (Self::Addr, u16) {
-        unsafe {
-            (sockaddr.sin6_addr.__in6_u.__u6_addr16.into(), sockaddr.sin6_port)
+        let mut segments = unsafe { sockaddr.sin6_addr.__in6_u.__u6_addr16 };
+        for i in 0..8 {
+            segments[i] = ntohs(segments[i]); // convert from big endian
         }
+        (segments.into(), ntohs(sockaddr.sin6_port))
+    }
+}
+
+/// IPv4 and IPv6 together, on one IPv6 socket with IPV6_V6ONLY off.
+///
+/// IPv4 addresses are passed to SCTP as v4-mapped IPv6 addresses
+/// (::ffff:a.b.c.d), and unmapped again on the way back, so one socket serves
+/// both families, and `bindx` and `connectx` lists may mix them.  Bind to
+/// `Ipv6Addr::UNSPECIFIED` to accept associations over either.  Addresses come
+/// back as `IpAddr`, or as `SocketAddr` from `accept_from` and `recv_from`.
+pub struct DualStack;
+impl Ip for DualStack {
+    type Addr = IpAddr;
+    type Sockaddr = sockaddr_in6;
+    fn pf() -> i32 { PF_INET6 as i32 }
+    fn to_sockaddr(addr: Self::Addr, port: u16) -> Self::Sockaddr {
+        let addr = match addr {
+            IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+            IpAddr::V6(v6) => v6,
+        };
+        Ipv6::to_sockaddr(addr, port)
+    }
+    fn to_sockaddr_wildcard() -> Self::Sockaddr {
+        Ipv6::to_sockaddr_wildcard()
+    }
+    fn from_sockaddr(sockaddr: Self::Sockaddr) -> (Self::Addr, u16) {
+        // usrsctp gives IPv4 peers as plain sockaddr_ins unless asked for
+        // mapped addresses, in the same (larger) space
+        if sockaddr.sin6_family as u32 == AF_INET {
+            let sa: sockaddr_in = unsafe {
+                ptr::read_unaligned(&sockaddr as *const sockaddr_in6 as *const sockaddr_in)
+            };
+            let (addr, port) = Ipv4::from_sockaddr(sa);
+            return (IpAddr::V4(addr), port);
+        }
+        let (addr, port) = Ipv6::from_sockaddr(sockaddr);
+        (unmap(addr), port)
+    }
+    fn has_family(af: u32) -> bool {
+        af == AF_INET || af == AF_INET6
+    }
+    fn dual_stack() -> bool {
+        true
+    }
+}
+
+// Unmap a v4-mapped IPv6 address
+fn unmap(addr: Ipv6Addr) -> IpAddr {
+    match addr.segments() {
+        [0, 0, 0, 0, 0, 0xffff, hi, lo] => {
+            IpAddr::V4(Ipv4Addr::new((hi >> 8) as u8, hi as u8, (lo >> 8) as u8, lo as u8))
+        },
+        _ => IpAddr::V6(addr),
+    }
+}
+
+/// An AF_CONN address.  This is an opaque identifier of a lower layer link
+/// (see `UsrSctp::register_conn`), rather than of either end of it: both ends
+/// bind to and connect to the same address, and are told apart by port.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ConnAddr(pub usize);
+
+/// The AF_CONN address family, in which usrsctp hands packets to the application
+/// to carry over a transport of its choosing.
+pub struct Conn;
+impl Ip for Conn {
+    type Addr = ConnAddr;
+    type Sockaddr = sockaddr_conn;
+    fn pf() -> i32 { AF_CONN as i32 }
+    fn to_sockaddr(addr: Self::Addr, port: u16) -> Self::Sockaddr {
+        // zeroed, since some platforms have an sconn_len field
+        let mut sconn: sockaddr_conn = unsafe { mem::zeroed() };
+        sconn.sconn_family = AF_CONN as u16;
+        sconn.sconn_port = htons(port);
+        sconn.sconn_addr = addr.0 as *mut c_void;
+        sconn
+    }
+    fn to_sockaddr_wildcard() -> Self::Sockaddr {
+        Self::to_sockaddr(ConnAddr(0), 0)
+    }
+    fn from_sockaddr(sockaddr: Self::Sockaddr) -> (Self::Addr, u16) {
+        (ConnAddr(sockaddr.sconn_addr as usize), ntohs(sockaddr.sconn_port))
+    }
+}
+
+/// Decode a sockaddr_storage (as found in options and notifications) holding an
+/// IPv4 or IPv6 address.  v4-mapped IPv6 addresses (as dual-stack sockets see
+/// IPv4 peers) come back as IPv4 ones.  Returns None for other address families.
+pub fn socket_addr_from_storage(ss: &sockaddr_storage) -> Option<SocketAddr> {
+    match ss.ss_family as u32 {
+        AF_INET => {
+            let sa: sockaddr_in = unsafe {
+                ptr::read_unaligned(ss as *const sockaddr_storage as *const sockaddr_in)
+            };
+            let (addr, port) = Ipv4::from_sockaddr(sa);
+            Some(SocketAddr::V4(SocketAddrV4::new(addr, port)))
+        },
+        AF_INET6 => {
+            let sa: sockaddr_in6 = unsafe {
+                ptr::read_unaligned(ss as *const sockaddr_storage as *const sockaddr_in6)
+            };
+            let scope_id = sa.sin6_scope_id;
+            let (addr, port) = Ipv6::from_sockaddr(sa);
+            match unmap(addr) {
+                IpAddr::V4(v4) => Some(SocketAddr::V4(SocketAddrV4::new(v4, port))),
+                IpAddr::V6(v6) => Some(SocketAddr::V6(SocketAddrV6::new(v6, port, 0, scope_id))),
+            }
+        },
+        _ => None,
+    }
+}
+
+/// Encode an IPv4 or IPv6 address as a sockaddr_storage (as used in options)
+pub fn socket_addr_to_storage(addr: &SocketAddr) -> sockaddr_storage {
+    match *addr {
+        SocketAddr::V4(ref v4) => Ipv4::to_sockaddr_storage(*v4.ip(), v4.port()),
+        SocketAddr::V6(ref v6) => {
+            let mut sa = Ipv6::to_sockaddr(*v6.ip(), v6.port());
+            sa.sin6_scope_id = v6.scope_id();
+            unsafe {
+                let mut ss: sockaddr_storage = mem::zeroed();
+                ptr::copy_nonoverlapping(&sa as *const sockaddr_in6 as *const u8,
+                                         &mut ss as *mut sockaddr_storage as *mut u8,
+                                         mem::size_of::<sockaddr_in6>());
+                ss
+            }
+        },
     }
 }
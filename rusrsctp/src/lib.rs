@@ -15,7 +15,7 @@
 //! let sctp = UsrSctp::new(Some(9899), true);
 //!
 //! // Create an IPv6 socket in one-to-one mode
-//! let mut socket = sctp.socket::<Ipv6>(false).unwrap();
+//! let mut socket = sctp.socket::<Ipv6, OneToOne>().unwrap();
 //!
 //! // Bind to wildcard address, port 5000
 //! socket.bind(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 5000).unwrap();
@@ -46,7 +46,7 @@
 //! let sctp = UsrSctp::new(Some(9899), true);
 //!
 //! // Create an IPv6 socket in one-to-one mode
-//! let mut socket = sctp.socket::<Ipv6>(false).unwrap();
+//! let mut socket = sctp.socket::<Ipv6, OneToOne>().unwrap();
 //!
 //! // Connect to a server (use a real IP address, and prepare to wait)
 //! # socket.set_non_blocking(true).unwrap();
@@ -60,13 +60,30 @@
 
 
 extern crate errno;
+extern crate libc;
+#[cfg(feature = "mio")]
+extern crate mio;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "bytes")]
+extern crate bytes;
+#[cfg(feature = "dtls")]
+extern crate openssl;
+#[cfg(feature = "futures")]
+extern crate futures_core;
+#[cfg(feature = "futures")]
+extern crate futures_sink;
+// for the tests of the tokio parts (the futures feature needs tokio too)
+#[cfg(all(test, feature = "tokio"))]
+extern crate futures;
 extern crate rusrsctp_sys;
 #[macro_use]
 extern crate bitflags;
 
 use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicUsize, AtomicBool, Ordering};
-use std::os::raw::{c_int, c_void, c_char};
+use std::os::raw::{c_void, c_char, c_int};
 use std::ptr;
 use std::thread;
 use std::time;
@@ -86,12 +103,102 @@ pub use self::types::*;
 mod option;
 pub use self::option::*;
 
-static SOCK_STREAM: c_int = 1;
-static SOCK_SEQPACKET: c_int = 5;
+mod mode;
+pub use self::mode::*;
+
+mod lifecycle;
+pub use self::lifecycle::*;
+
+mod dualstack;
+pub use self::dualstack::*;
+
+mod connect;
+pub use self::connect::*;
+
+mod notification;
+pub use self::notification::*;
+
+mod auth;
+pub use self::auth::*;
+
+mod sched;
+pub use self::sched::*;
+
+mod cc;
+pub use self::cc::*;
+
+mod interleave;
+pub use self::interleave::*;
+
+mod asconf;
+pub use self::asconf::*;
+
+mod encaps;
+pub use self::encaps::*;
+
+mod rawip;
+pub use self::rawip::*;
+
+mod callback;
+pub use self::callback::*;
+
+mod recvbuf;
+pub use self::recvbuf::*;
+
+mod readiness;
+pub use self::readiness::*;
+
+mod conn;
+pub use self::conn::*;
+
+mod endpoint;
+pub use self::endpoint::*;
+
+mod stream;
+pub use self::stream::*;
+
+mod writer;
+pub use self::writer::*;
+
+mod assembler;
+pub use self::assembler::*;
+
+mod reset;
+pub use self::reset::*;
+
+mod datachannel;
+pub use self::datachannel::*;
+
+mod unix;
+pub use self::unix::*;
+
+mod framed;
+pub use self::framed::*;
+
+#[cfg(feature = "dtls")]
+mod dtls;
+#[cfg(feature = "dtls")]
+pub use self::dtls::*;
+
+#[cfg(feature = "tokio")]
+mod async_sctp;
+#[cfg(feature = "tokio")]
+pub use self::async_sctp::*;
+
+#[cfg(feature = "futures")]
+mod messages;
+#[cfg(feature = "futures")]
+pub use self::messages::*;
 
 static REFCOUNT: AtomicUsize = AtomicUsize::new(0);
 // We set this true AFTER intialization is complete (we bump REFCOUNT before)
 static INITIALIZED: AtomicBool = AtomicBool::new(false);
+// Whether SCTP was initialized without its own threads (see UsrSctp::new_nothreads)
+static NO_THREADS: AtomicBool = AtomicBool::new(false);
+// Nanoseconds passed to UsrSctp::advance which did not yet make up a millisecond
+static CLOCK_CARRY_NANOS: Mutex<u32> = Mutex::new(0);
+// The time last passed to UsrSctp::advance_to
+static CLOCK_NOW: Mutex<Option<time::Instant>> = Mutex::new(None);
 
 pub fn htons(v: u16) -> u16 {
     unsafe {
@@ -131,19 +238,48 @@ impl UsrSctp {
     /// own notion of ports independent of this UDP layer port.
     /// If another thread (or the current one) already started SCTP, `port` will
     /// be ignored and the already setup SCTP will be used.
+    /// SCTP over IP needs raw sockets; see `with_policy` to check for them first.
+    /// Panics if SCTP is already running without threads (see `new_nothreads`).
     pub fn new(port: Option<u16>, inner_debug: bool) -> UsrSctp
+    {
+        UsrSctp::init(port, inner_debug, false)
+    }
+
+    /// Initialize SCTP without any threads of its own.
+    /// Nothing happens in the background: timers only run when `advance` is
+    /// called, so the application owns the clock.  Since no threads read from
+    /// the network either, this is meant to be used with an AF_CONN transport.
+    ///
+    /// There is only one SCTP stack per process, so the two modes cannot be
+    /// mixed: this panics if SCTP is already running with threads (by `new`),
+    /// and `new` panics while SCTP runs without.  Tests of this mode belong in a
+    /// test binary of their own.
+    pub fn new_nothreads(port: Option<u16>, inner_debug: bool) -> UsrSctp
+    {
+        UsrSctp::init(port, inner_debug, true)
+    }
+
+    fn init(port: Option<u16>, inner_debug: bool, no_threads: bool) -> UsrSctp
     {
         // If it was 0, make it 1 and enter this block
         if REFCOUNT.fetch_add(1, Ordering::SeqCst) == 0 {
             // We were the first!  We get to initialize
+            NO_THREADS.store(no_threads, Ordering::SeqCst);
+            let debug_printf = if inner_debug {
+                Some(printf as unsafe extern "C" fn(*const c_char, ...))
+            } else {
+                None
+            }; // debug_printf using C printf()
             unsafe {
-                usrsctp_init(port.unwrap_or(0),
-                             None, // conn_output not supported (yet)
-                             if inner_debug {
-                                 Some(printf)
-                             } else {
-                                 None
-                             }); // debug_printf using C printf()
+                if no_threads {
+                    usrsctp_init_nothreads(port.unwrap_or(0),
+                                           Some(conn_output), // for AF_CONN links
+                                           debug_printf);
+                } else {
+                    usrsctp_init(port.unwrap_or(0),
+                                 Some(conn_output), // for AF_CONN links
+                                 debug_printf);
+                }
             }
             INITIALIZED.store(true, Ordering::SeqCst);
         } else {
@@ -157,9 +293,57 @@ impl UsrSctp {
                     panic!("Waiting >1s for SCTP to initialize");
                 }
             }
+            if NO_THREADS.load(Ordering::SeqCst) != no_threads {
+                REFCOUNT.fetch_sub(1, Ordering::SeqCst);
+                panic!("SCTP is already running {} threads",
+                       if no_threads { "with" } else { "without" });
+            }
         }
         UsrSctp {}
     }
+
+    /// Advance SCTP's clock by `elapsed`, running any timers which expire.
+    /// Only for SCTP started with `new_nothreads`; otherwise SCTP keeps its own
+    /// time (and this panics).
+    pub fn advance(&self, elapsed: time::Duration) {
+        assert!(NO_THREADS.load(Ordering::SeqCst),
+                "UsrSctp::advance() requires UsrSctp::new_nothreads()");
+        // usrsctp counts in milliseconds; carry the rest over to the next call
+        let millis = {
+            let mut carry = CLOCK_CARRY_NANOS.lock().unwrap_or_else(|e| e.into_inner());
+            let nanos = elapsed.as_secs() as u128 * 1_000_000_000
+                + elapsed.subsec_nanos() as u128 + *carry as u128;
+            *carry = (nanos % 1_000_000) as u32;
+            nanos / 1_000_000
+        };
+        if millis > 0 {
+            unsafe {
+                usrsctp_handle_timers(millis as u32);
+            }
+        }
+    }
+
+    /// Advance SCTP's clock to `now`, by however long passed since the last call.
+    /// The first call only sets the starting time.  Like `advance`, this is only
+    /// for SCTP started with `new_nothreads`.
+    pub fn advance_to(&self, now: time::Instant) {
+        let elapsed = {
+            let mut clock = CLOCK_NOW.lock().unwrap_or_else(|e| e.into_inner());
+            let elapsed = clock.map(|then| now.saturating_duration_since(then));
+            if clock.map(|then| now > then).unwrap_or(true) {
+                *clock = Some(now);
+            }
+            elapsed
+        };
+        if let Some(elapsed) = elapsed {
+            self.advance(elapsed);
+        }
+    }
+
+    /// The time last passed to `advance_to`, if any
+    pub fn now(&self) -> Option<time::Instant> {
+        *CLOCK_NOW.lock().unwrap_or_else(|e| e.into_inner())
+    }
 }
 
 impl Drop for UsrSctp {
@@ -175,46 +359,73 @@ impl Drop for UsrSctp {
 }
 
 impl UsrSctp {
-    pub fn socket<'a, T: 'a + Ip>(&'a self, one_to_many: bool) -> Result<Socket<'a, T>, Errno> {
+    /// Create a socket of address family `T`, in mode `M` (`OneToOne` or `OneToMany`)
+    pub fn socket<'a, T: 'a + Ip, M: Mode>(&'a self) -> Result<Socket<'a, T, M>, Errno> {
         let so = unsafe {
             usrsctp_socket(
                 T::pf(),
-                if one_to_many { SOCK_SEQPACKET } else { SOCK_STREAM }, // type
+                M::sock_type(), // type
                 IPPROTO_SCTP as i32,
-                None, // callback API (receive_cb) not supported (yet)
-                None, // callback API (send_cb) not supported (yet)
-                0, // sb_threshold is irrelevant without send_cp
-                ptr::null_mut() // ulp_info is irrelevant without receive_cp
+                None, // see socket_with_callbacks() for the callback API
+                None,
+                0, // sb_threshold is irrelevant without send_cb
+                ptr::null_mut() // ulp_info is irrelevant without receive_cb
             )
         };
         if so.is_null() {
             Err(errno::errno())
         } else {
-            Ok(Socket {
+            let mut socket = Socket {
                 inner: so,
+                callbacks: None,
+                readiness: None,
                 _ip: PhantomData,
-            })
+                _mode: PhantomData,
+            };
+            socket.init_family()?;
+            Ok(socket)
         }
     }
 }
 
 #[allow(dead_code)]
-pub struct Socket<'a, T: 'a + Ip> {
+pub struct Socket<'a, T: 'a + Ip, M: Mode = OneToOne> {
     inner: *mut socket,
+    // Callback mode state, registered under the id in ulp_info
+    callbacks: Option<Arc<Callbacks<T>>>,
+    // Readiness bridge, and the id of its eventfd handed to usrsctp as upcall arg
+    readiness: Option<(Readiness, usize)>,
     // Type parameterize a Socket with Ip (v4 or v6), while also using a reference
     // with the lifetime of UsrSctp so that socket objects cannot outlive UsrSctp.
     _ip: PhantomData<&'a T>,
+    // Whether the socket is one-to-one or one-to-many, which decides the
+    // operations it supports
+    _mode: PhantomData<M>,
 }
 
-impl<'a, T: 'a + Ip> Drop for Socket<'a, T> {
+// SAFETY: usrsctp sockets are not tied to a thread (its own threads use them
+// all the time, under its locks), and T and M are only marker types.
+unsafe impl<'a, T: 'a + Ip, M: Mode> Send for Socket<'a, T, M> {}
+
+impl<'a, T: 'a + Ip, M: Mode> Drop for Socket<'a, T, M> {
     fn drop(&mut self) {
         unsafe {
+            if let Some((_, arg)) = self.readiness.take() {
+                usrsctp_set_upcall(self.inner, None, ptr::null_mut());
+                deregister_upcall_arg(arg);
+            }
+            if self.callbacks.is_some() {
+                // Callbacks may still fire while the association shuts down.  Once
+                // the last socket sharing our callbacks is gone they find nothing
+                // registered, but there is no need to look.
+                usrsctp_set_ulpinfo(self.inner, ptr::null_mut());
+            }
             usrsctp_close(self.inner);
         }
     }
 }
 
-impl<'a, T: 'a + Ip> Socket<'a, T> {
+impl<'a, T: 'a + Ip, M: Mode> Socket<'a, T, M> {
     pub fn bind(&mut self, addr: T::Addr, port: u16) -> Result<(), Errno> {
         let mut sa = T::to_sockaddr(addr, port);
         let rval = unsafe {
@@ -232,11 +443,26 @@ impl<'a, T: 'a + Ip> Socket<'a, T> {
         }
     }
 
-    pub fn listen(&mut self, backlog: i32) -> Result<(), Errno> {
+    /// Add addresses to (or remove addresses from) the set this socket is bound
+    /// to, for multihoming.  All addresses must use the port the socket is bound
+    /// to.  With ASCONF enabled, the change is announced to the peers of existing
+    /// associations.
+    pub fn bindx(&mut self, addrs: &[(T::Addr, u16)], op: Bindx) -> Result<(), Errno>
+    {
+        let mut sas: Vec<T::Sockaddr> = addrs.iter()
+            .map(|&(addr, port)| T::to_sockaddr(addr, port))
+            .collect();
+        let flags = match op {
+            Bindx::Add => SCTP_BINDX_ADD_ADDR,
+            Bindx::Remove => SCTP_BINDX_REM_ADDR,
+        };
         let rval = unsafe {
-            usrsctp_listen(
+            // usrsctp expects the sockaddrs packed one after another
+            usrsctp_bindx(
                 self.inner,
-                backlog
+                sas.as_mut_ptr() as *mut c_void as *mut sockaddr,
+                sas.len() as i32,
+                flags as i32
             )
         };
         if rval < 0 {
@@ -246,26 +472,41 @@ impl<'a, T: 'a + Ip> Socket<'a, T> {
         }
     }
 
-    pub fn accept(&mut self) -> Result<(T::Addr, u16, Socket<'a, T>), Errno> {
-        // space for return value
-        let mut sa: T::Sockaddr = T::to_sockaddr_wildcard();
-        let mut sa_len: u32 = 0;
-        let so = unsafe {
-            // We cannot transmute, we have to pass the pointer through the void.
-            usrsctp_accept(
+    /// Connect to a multihomed peer, at any of `addrs` (which must all use the
+    /// same port).  Returns the id of the new association.
+    pub fn connectx(&mut self, addrs: &[(T::Addr, u16)]) -> Result<AssocId, Errno>
+    {
+        let mut sas: Vec<T::Sockaddr> = addrs.iter()
+            .map(|&(addr, port)| T::to_sockaddr(addr, port))
+            .collect();
+        let mut assoc_id: AssocId = 0;
+        let rval = unsafe {
+            // usrsctp expects the sockaddrs packed one after another
+            usrsctp_connectx(
                 self.inner,
-                &mut sa as *mut T::Sockaddr as *mut c_void as *mut sockaddr,
-                &mut sa_len as *mut u32
+                sas.as_mut_ptr() as *mut c_void as *mut sockaddr,
+                sas.len() as i32,
+                &mut assoc_id as *mut AssocId
             )
         };
-        if so.is_null() {
+        if rval < 0 {
             Err(errno::errno())
         } else {
-            let (addr, port) = T::from_sockaddr(sa);
-            Ok((addr, port, Socket {
-                inner: so,
-                _ip: PhantomData,
-            }))
+            Ok(assoc_id)
+        }
+    }
+
+    pub fn listen(&mut self, backlog: i32) -> Result<(), Errno> {
+        let rval = unsafe {
+            usrsctp_listen(
+                self.inner,
+                backlog
+            )
+        };
+        if rval < 0 {
+            Err(errno::errno())
+        } else {
+            Ok(())
         }
     }
 
@@ -351,10 +592,107 @@ impl<'a, T: 'a + Ip> Socket<'a, T> {
         }
     }
 
-    /// Send data.
-    /// NOTE: usrsctp limits addr to zero or one.  SCTP itself allows multiple
-    /// addresses.  So we are limited by `usrsctp` on that point.
-    pub fn sendv(&mut self,
+    pub fn getsockopt<O: SctpOption>(&mut self, mut option: O) -> Result<O, Errno>
+    {
+        let mut len = option.value_size();
+        let rval = unsafe {
+            usrsctp_getsockopt(
+                self.inner,
+                IPPROTO_SCTP as i32,
+                option.c_name(),
+                option.value_ptr_mut() as *mut O::Value as *mut c_void,
+                &mut len as *mut u32
+            )
+        };
+        if rval < 0 {
+            Err(errno::errno())
+        } else {
+            Ok(option)
+        }
+    }
+
+    // Like setsockopt, for options with trailing variable length data
+    fn setsockopt_buf(&mut self, c_name: u32, buf: &OptBuf) -> Result<(), Errno>
+    {
+        let rval = unsafe {
+            usrsctp_setsockopt(
+                self.inner,
+                IPPROTO_SCTP as i32,
+                c_name as i32,
+                buf.as_ptr(),
+                buf.len() as u32
+            )
+        };
+        if rval < 0 {
+            Err(errno::errno())
+        } else {
+            Ok(())
+        }
+    }
+
+    // Like getsockopt, for options with trailing variable length data.  The
+    // buffer is truncated to the length usrsctp filled in.
+    fn getsockopt_buf(&mut self, c_name: u32, buf: &mut OptBuf) -> Result<(), Errno>
+    {
+        let mut len = buf.len() as u32;
+        let rval = unsafe {
+            usrsctp_getsockopt(
+                self.inner,
+                IPPROTO_SCTP as i32,
+                c_name as i32,
+                buf.as_mut_ptr(),
+                &mut len as *mut u32
+            )
+        };
+        if rval < 0 {
+            Err(errno::errno())
+        } else {
+            buf.truncate(len as usize);
+            Ok(())
+        }
+    }
+
+    /// Subscribe to (or unsubscribe from) notifications of the given type.
+    /// On one-to-many sockets, use SCTP_FUTURE_ASSOC (or SCTP_ALL_ASSOC, etc) as
+    /// `assoc_id` to affect more than one association.  Notifications are then
+    /// returned by `recvv` with `MsgFlags::NOTIFICATION` set.
+    pub fn subscribe(&mut self, assoc_id: AssocId, event: EventType, on: bool)
+                     -> Result<(), Errno>
+    {
+        self.setsockopt(Event(sctp_event {
+            se_assoc_id: assoc_id,
+            se_type: event.c_type(),
+            se_on: if on { 1 } else { 0 },
+        }))
+    }
+
+    // Set up a new socket as its address family needs
+    fn init_family(&mut self) -> Result<(), Errno> {
+        if !T::dual_stack() {
+            return Ok(());
+        }
+        let off: c_int = 0;
+        let rval = unsafe {
+            usrsctp_setsockopt(
+                self.inner,
+                IPPROTO_IPV6 as i32,
+                IPV6_V6ONLY as i32,
+                &off as *const c_int as *const c_void,
+                mem::size_of::<c_int>() as u32
+            )
+        };
+        if rval < 0 && errno::errno().0 != libc::ENOPROTOOPT {
+            Err(errno::errno())
+        } else {
+            // Builds without IPv6 socket options never set IPV6_V6ONLY
+            Ok(())
+        }
+    }
+
+    // Send data, as both modes' `sendv` do.
+    // NOTE: usrsctp limits addr to zero or one.  SCTP itself allows multiple
+    // addresses.  So we are limited by `usrsctp` on that point.
+    pub(crate) fn sendv_inner(&mut self,
                  data: &[u8],
                  addr: Option<(T::Addr, u16)>,
                  snd_info: Option<SndInfo>,
@@ -426,4 +764,99 @@ impl<'a, T: 'a + Ip> Socket<'a, T> {
             Ok(rval as usize)
         }
     }
+
+    /// Receive data.
+    /// Returns the length received, the sender's address (if known), the receive
+    /// info (only if the `RecvRcvInfo` option is on), and the message flags.  If the
+    /// flags contain `MsgFlags::NOTIFICATION`, the data is a notification (see
+    /// `Notification::parse`) rather than a user message.  If the flags do not
+    /// contain `MsgFlags::EOR`, only part of the message was returned.
+    pub fn recvv(&mut self, data: &mut [u8])
+                 -> Result<(usize, Option<(T::Addr, u16)>, Option<RcvInfo>, MsgFlags), Errno>
+    {
+        // space for return values
+        let mut sa: T::Sockaddr = T::to_sockaddr_wildcard();
+        let mut sa_len = mem::size_of::<T::Sockaddr>() as u32;
+        // Room for both rcvinfo and nxtinfo, in case SCTP_RECVNXTINFO was also set
+        let mut info: sctp_recvv_rn = unsafe { mem::zeroed() };
+        let mut info_len = mem::size_of::<sctp_recvv_rn>() as u32;
+        let mut info_type: u32 = SCTP_RECVV_NOINFO;
+        let mut flags: i32 = 0;
+
+        let rval = unsafe {
+            usrsctp_recvv(
+                self.inner,
+                data.as_mut_ptr() as *mut c_void,
+                data.len(),
+                &mut sa as *mut T::Sockaddr as *mut c_void as *mut sockaddr,
+                &mut sa_len as *mut u32,
+                &mut info as *mut sctp_recvv_rn as *mut c_void,
+                &mut info_len as *mut u32,
+                &mut info_type as *mut u32,
+                &mut flags as *mut i32)
+        };
+        if rval < 0 {
+            return Err(errno::errno());
+        }
+
+        let from = if sa_len > 0 { Some(T::from_sockaddr(sa)) } else { None };
+        // rcvinfo comes first in sctp_recvv_rn, so either way it is in the same place
+        let rcv_info = match info_type {
+            SCTP_RECVV_RCVINFO | SCTP_RECVV_RN => {
+                Some(RcvInfo::from_sctp_rcvinfo(info.recvv_rcvinfo))
+            },
+            _ => None,
+        };
+        Ok((rval as usize, from, rcv_info, MsgFlags::from_bits_truncate(flags as u32)))
+    }
+}
+
+/// Operations only a one-to-one socket supports
+impl<'a, T: 'a + Ip> Socket<'a, T, OneToOne> {
+    pub fn accept(&mut self) -> Result<(T::Addr, u16, Socket<'a, T>), Errno> {
+        // space for return value
+        let mut sa: T::Sockaddr = T::to_sockaddr_wildcard();
+        let mut sa_len = mem::size_of::<T::Sockaddr>() as u32;
+        let so = unsafe {
+            // We cannot transmute, we have to pass the pointer through the void.
+            usrsctp_accept(
+                self.inner,
+                &mut sa as *mut T::Sockaddr as *mut c_void as *mut sockaddr,
+                &mut sa_len as *mut u32
+            )
+        };
+        if so.is_null() {
+            Err(errno::errno())
+        } else {
+            if let Some(ref callbacks) = self.callbacks {
+                unsafe {
+                    usrsctp_set_ulpinfo(so, Callbacks::ulp_info(callbacks));
+                }
+            }
+            let (addr, port) = T::from_sockaddr(sa);
+            Ok((addr, port, Socket {
+                inner: so,
+                callbacks: self.callbacks.clone(),
+                readiness: None,
+                _ip: PhantomData,
+                _mode: PhantomData,
+            }))
+        }
+    }
+
+    /// Send data.  `snd_info.assoc_id` is ignored, as there is only the one
+    /// association, and so is `SctpFlags::SENDALL`.
+    /// NOTE: usrsctp limits addr to zero or one.  SCTP itself allows multiple
+    /// addresses.  So we are limited by `usrsctp` on that point.
+    pub fn sendv(&mut self,
+                 data: &[u8],
+                 addr: Option<(T::Addr, u16)>,
+                 snd_info: Option<SndInfo>,
+                 pr_info: Option<PrInfo>,
+                 auth_info: Option<AuthInfo>,
+                 flags: MsgFlags) -> Result<usize, Errno>
+    {
+        let snd_info = snd_info.map(OneToOne::filter_snd_info);
+        self.sendv_inner(data, addr, snd_info, pr_info, auth_info, flags)
+    }
 }
@@ -0,0 +1,248 @@
+
+use std::fmt;
+use errno::Errno;
+use libc;
+use super::{UsrSctp, Socket, Ip, Mode, OneToOne, OneToMany, SendTo, Bindx, Shutdown, SndInfo,
+            PrInfo, AuthInfo, RcvInfo, MsgFlags};
+
+// Each state wraps a raw socket, which stays available as an escape hatch
+macro_rules! socket_state {
+    ($name:ident) => {
+        impl<'a, T: 'a + Ip, M: Mode> $name<'a, T, M> {
+            /// The raw socket, for setting options and the like
+            pub fn get_ref(&self) -> &Socket<'a, T, M> {
+                &self.socket
+            }
+
+            /// The raw socket.  Nothing stops it being used out of turn.
+            pub fn get_mut(&mut self) -> &mut Socket<'a, T, M> {
+                &mut self.socket
+            }
+
+            /// Give up the typestate and get the raw socket back
+            pub fn into_inner(self) -> Socket<'a, T, M> {
+                self.socket
+            }
+        }
+
+        // So that a failed transition (which hands the socket back) can be unwrapped
+        impl<'a, T: 'a + Ip, M: Mode> fmt::Debug for $name<'a, T, M> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_struct(stringify!($name)).finish_non_exhaustive()
+            }
+        }
+    }
+}
+
+/// A socket not yet bound or connected, from `UsrSctp::unbound`.
+///
+/// This and the other socket states (`BoundSocket`, `Listener`, `Connecting`,
+/// `Connected`) only offer the operations which make sense in that state, so
+/// that e.g. listening before binding, or sending before connecting, does not
+/// compile.  Each transition consumes the socket; if it fails, the socket is
+/// handed back in the state it was in, along with the error.
+pub struct UnboundSocket<'a, T: 'a + Ip, M: Mode = OneToOne> {
+    socket: Socket<'a, T, M>,
+}
+socket_state!(UnboundSocket);
+
+/// A socket bound to a local address, which may go on to listen or connect
+pub struct BoundSocket<'a, T: 'a + Ip, M: Mode = OneToOne> {
+    socket: Socket<'a, T, M>,
+}
+socket_state!(BoundSocket);
+
+/// A listening socket.  One-to-one listeners accept associations; one-to-many
+/// listeners send and receive on all of theirs.
+pub struct Listener<'a, T: 'a + Ip, M: Mode = OneToOne> {
+    socket: Socket<'a, T, M>,
+}
+socket_state!(Listener);
+
+/// A one-to-one socket whose association is being set up, from `start_connect`
+pub struct Connecting<'a, T: 'a + Ip, M: Mode = OneToOne> {
+    socket: Socket<'a, T, M>,
+}
+socket_state!(Connecting);
+
+/// How far `Connecting::poll_connect` got
+pub enum ConnectPoll<'a, T: 'a + Ip> {
+    /// The association is up
+    Up(Connected<'a, T, OneToOne>),
+    /// The association is still being set up
+    InProgress(Connecting<'a, T, OneToOne>),
+}
+
+/// A socket with an association
+pub struct Connected<'a, T: 'a + Ip, M: Mode = OneToOne> {
+    socket: Socket<'a, T, M>,
+}
+socket_state!(Connected);
+
+impl UsrSctp {
+    /// Create a socket which tracks its lifecycle in its type
+    pub fn unbound<'a, T: 'a + Ip, M: Mode>(&'a self) -> Result<UnboundSocket<'a, T, M>, Errno> {
+        Ok(UnboundSocket {
+            socket: self.socket::<T, M>()?,
+        })
+    }
+}
+
+impl<'a, T: 'a + Ip, M: Mode> UnboundSocket<'a, T, M> {
+    /// Start tracking a raw socket, which must be neither bound nor connected
+    pub fn from_socket(socket: Socket<'a, T, M>) -> UnboundSocket<'a, T, M> {
+        UnboundSocket { socket: socket }
+    }
+
+    pub fn bind(mut self, addr: T::Addr, port: u16)
+                -> Result<BoundSocket<'a, T, M>, (Errno, UnboundSocket<'a, T, M>)>
+    {
+        match self.socket.bind(addr, port) {
+            Ok(()) => Ok(BoundSocket { socket: self.socket }),
+            Err(e) => Err((e, self)),
+        }
+    }
+
+    /// Connect from an address and port picked by SCTP.  The socket must be
+    /// blocking; see `start_connect` otherwise.
+    pub fn connect(mut self, addr: T::Addr, port: u16)
+                   -> Result<Connected<'a, T, M>, (Errno, UnboundSocket<'a, T, M>)>
+    {
+        match self.socket.connect(addr, port) {
+            Ok(()) => Ok(Connected { socket: self.socket }),
+            Err(e) => Err((e, self)),
+        }
+    }
+}
+
+impl<'a, T: 'a + Ip> UnboundSocket<'a, T, OneToOne> {
+    /// Start connecting without blocking, from an address and port picked by
+    /// SCTP.  See `Connecting::poll_connect`.
+    pub fn start_connect(mut self, addr: T::Addr, port: u16)
+                         -> Result<Connecting<'a, T>, (Errno, UnboundSocket<'a, T>)>
+    {
+        match start_connect(&mut self.socket, addr, port) {
+            Ok(()) => Ok(Connecting { socket: self.socket }),
+            Err(e) => Err((e, self)),
+        }
+    }
+}
+
+impl<'a, T: 'a + Ip, M: Mode> BoundSocket<'a, T, M> {
+    /// Add or remove local addresses, for multihoming
+    pub fn bindx(&mut self, addrs: &[(T::Addr, u16)], op: Bindx) -> Result<(), Errno> {
+        self.socket.bindx(addrs, op)
+    }
+
+    pub fn listen(mut self, backlog: i32)
+                  -> Result<Listener<'a, T, M>, (Errno, BoundSocket<'a, T, M>)>
+    {
+        match self.socket.listen(backlog) {
+            Ok(()) => Ok(Listener { socket: self.socket }),
+            Err(e) => Err((e, self)),
+        }
+    }
+
+    /// Connect.  The socket must be blocking; see `start_connect` otherwise.
+    pub fn connect(mut self, addr: T::Addr, port: u16)
+                   -> Result<Connected<'a, T, M>, (Errno, BoundSocket<'a, T, M>)>
+    {
+        match self.socket.connect(addr, port) {
+            Ok(()) => Ok(Connected { socket: self.socket }),
+            Err(e) => Err((e, self)),
+        }
+    }
+}
+
+impl<'a, T: 'a + Ip> BoundSocket<'a, T, OneToOne> {
+    /// Start connecting without blocking.  See `Connecting::poll_connect`.
+    pub fn start_connect(mut self, addr: T::Addr, port: u16)
+                         -> Result<Connecting<'a, T>, (Errno, BoundSocket<'a, T>)>
+    {
+        match start_connect(&mut self.socket, addr, port) {
+            Ok(()) => Ok(Connecting { socket: self.socket }),
+            Err(e) => Err((e, self)),
+        }
+    }
+}
+
+// Connect without blocking, which is under way once this returns
+fn start_connect<'a, T: 'a + Ip>(socket: &mut Socket<'a, T>, addr: T::Addr, port: u16)
+                                 -> Result<(), Errno>
+{
+    socket.set_non_blocking(true)?;
+    match socket.connect(addr, port) {
+        Err(ref e) if e.0 != libc::EINPROGRESS => Err(*e),
+        _ => Ok(()),
+    }
+}
+
+impl<'a, T: 'a + Ip> Connecting<'a, T, OneToOne> {
+    /// Check on the association (see `Socket::poll_connect`).  Once it is up, the
+    /// socket is `Connected`, and still non-blocking.  If setup failed, the error
+    /// comes back with the socket.
+    pub fn poll_connect(mut self) -> Result<ConnectPoll<'a, T>, (Errno, Connecting<'a, T>)> {
+        match self.socket.poll_connect() {
+            Ok(true) => Ok(ConnectPoll::Up(Connected { socket: self.socket })),
+            Ok(false) => Ok(ConnectPoll::InProgress(self)),
+            Err(e) => Err((e, self)),
+        }
+    }
+}
+
+impl<'a, T: 'a + Ip> Listener<'a, T, OneToOne> {
+    pub fn accept(&mut self) -> Result<(T::Addr, u16, Connected<'a, T, OneToOne>), Errno> {
+        let (addr, port, socket) = self.socket.accept()?;
+        Ok((addr, port, Connected { socket: socket }))
+    }
+}
+
+impl<'a, T: 'a + Ip> Listener<'a, T, OneToMany> {
+    /// See `Socket::sendv` (for one-to-many sockets)
+    pub fn sendv(&mut self, data: &[u8], to: SendTo<T>, snd_info: Option<SndInfo>,
+                 pr_info: Option<PrInfo>, auth_info: Option<AuthInfo>, flags: MsgFlags)
+                 -> Result<usize, Errno>
+    {
+        self.socket.sendv(data, to, snd_info, pr_info, auth_info, flags)
+    }
+
+    /// See `Socket::recvv`
+    pub fn recvv(&mut self, data: &mut [u8])
+                 -> Result<(usize, Option<(T::Addr, u16)>, Option<RcvInfo>, MsgFlags), Errno>
+    {
+        self.socket.recvv(data)
+    }
+}
+
+impl<'a, T: 'a + Ip> Connected<'a, T, OneToOne> {
+    /// See `Socket::sendv` (for one-to-one sockets)
+    pub fn sendv(&mut self, data: &[u8], addr: Option<(T::Addr, u16)>, snd_info: Option<SndInfo>,
+                 pr_info: Option<PrInfo>, auth_info: Option<AuthInfo>, flags: MsgFlags)
+                 -> Result<usize, Errno>
+    {
+        self.socket.sendv(data, addr, snd_info, pr_info, auth_info, flags)
+    }
+}
+
+impl<'a, T: 'a + Ip> Connected<'a, T, OneToMany> {
+    /// See `Socket::sendv` (for one-to-many sockets)
+    pub fn sendv(&mut self, data: &[u8], to: SendTo<T>, snd_info: Option<SndInfo>,
+                 pr_info: Option<PrInfo>, auth_info: Option<AuthInfo>, flags: MsgFlags)
+                 -> Result<usize, Errno>
+    {
+        self.socket.sendv(data, to, snd_info, pr_info, auth_info, flags)
+    }
+}
+
+impl<'a, T: 'a + Ip, M: Mode> Connected<'a, T, M> {
+    /// See `Socket::recvv`
+    pub fn recvv(&mut self, data: &mut [u8])
+                 -> Result<(usize, Option<(T::Addr, u16)>, Option<RcvInfo>, MsgFlags), Errno>
+    {
+        self.socket.recvv(data)
+    }
+
+    pub fn shutdown(&mut self, shutdown: Shutdown) -> Result<(), Errno> {
+        self.socket.shutdown(shutdown)
+    }
+}
@@ -0,0 +1,132 @@
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use errno::Errno;
+use futures_core::Stream;
+use futures_sink::Sink;
+use super::{AsyncSctpStream, Ip, Message, MessageAssembler, OutgoingMessage, MsgFlags, RecvRcvInfo,
+            Shutdown};
+
+// How much to receive at a time.  Larger messages are received in several parts.
+const RECV_CHUNK: usize = 65536;
+// Messages received larger than this are refused
+const MAX_MESSAGE: usize = 16 * 1024 * 1024;
+
+/// A `Stream` of complete received messages, and a `Sink` of messages to send,
+/// over an `AsyncSctpStream`.
+///
+/// Each item of the stream is a whole message, reassembled (by a
+/// `MessageAssembler`) if it was received in several parts, or a notification
+/// (with `MsgFlags::NOTIFICATION` set), if any were subscribed to.  A message
+/// larger than 16 MiB is refused with an `EMSGSIZE` item.  The stream ends when
+/// the peer shuts down.  Closing the sink starts a graceful shutdown.
+pub struct MessageStream<'a, T: 'a + Ip> {
+    stream: AsyncSctpStream<'a, T>,
+    buf: Vec<u8>,
+    // the parts received so far of messages not yet complete
+    assembler: MessageAssembler,
+    // a message accepted by start_send but not yet sent
+    pending: Option<OutgoingMessage>,
+    eof: bool,
+}
+
+impl<'a, T: 'a + Ip> AsyncSctpStream<'a, T> {
+    /// Use this stream as a `Stream` and `Sink` of messages.  This turns on the
+    /// `RecvRcvInfo` option, so that received messages carry their stream and PPID.
+    pub fn into_messages(mut self) -> Result<MessageStream<'a, T>, Errno> {
+        self.get_mut().setsockopt(RecvRcvInfo(1))?;
+        Ok(MessageStream {
+            stream: self,
+            buf: vec![0; RECV_CHUNK],
+            assembler: MessageAssembler::new(MAX_MESSAGE),
+            pending: None,
+            eof: false,
+        })
+    }
+}
+
+impl<'a, T: 'a + Ip> MessageStream<'a, T> {
+    pub fn get_ref(&self) -> &AsyncSctpStream<'a, T> {
+        &self.stream
+    }
+
+    pub fn get_mut(&mut self) -> &mut AsyncSctpStream<'a, T> {
+        &mut self.stream
+    }
+
+    /// Get the stream back.  Any partially received or unsent message is lost.
+    pub fn into_inner(self) -> AsyncSctpStream<'a, T> {
+        self.stream
+    }
+
+    fn poll_send_pending(&mut self, cx: &mut Context) -> Poll<Result<(), Errno>> {
+        let result = match self.pending {
+            None => return Poll::Ready(Ok(())),
+            Some(ref msg) => self.stream.poll_op(cx, |socket| {
+                socket.sendv(&msg.data, None, msg.snd_info, msg.pr_info, msg.auth_info,
+                             MsgFlags::empty())
+            }),
+        };
+        match result {
+            Poll::Ready(result) => {
+                self.pending = None;
+                Poll::Ready(result.map(|_| ()))
+            },
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<'a, T: 'a + Ip> Stream for MessageStream<'a, T> {
+    type Item = Result<Message, Errno>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        while !this.eof {
+            let (len, rcv_info, flags) = {
+                let buf = &mut this.buf;
+                match this.stream.poll_op(cx, |socket| socket.recvv(&mut buf[..])) {
+                    Poll::Ready(Ok((len, _from, rcv_info, flags))) => (len, rcv_info, flags),
+                    Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
+                    Poll::Pending => return Poll::Pending,
+                }
+            };
+            if len == 0 && !flags.contains(MsgFlags::NOTIFICATION) {
+                // SCTP messages are never empty, so this is the end
+                this.eof = true;
+                break;
+            }
+            match this.assembler.push(&this.buf[..len], rcv_info, flags) {
+                Ok(Some(message)) => return Poll::Ready(Some(Ok(message))),
+                Ok(None) => (),
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+impl<'a, T: 'a + Ip> Sink<OutgoingMessage> for MessageStream<'a, T> {
+    type Error = Errno;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Errno>> {
+        self.get_mut().poll_send_pending(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: OutgoingMessage) -> Result<(), Errno> {
+        self.get_mut().pending = Some(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Errno>> {
+        self.get_mut().poll_send_pending(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Errno>> {
+        let this = self.get_mut();
+        match this.poll_send_pending(cx) {
+            Poll::Ready(Ok(())) => Poll::Ready(this.stream.get_mut().shutdown(Shutdown::Wr)),
+            other => other,
+        }
+    }
+}
@@ -0,0 +1,105 @@
+
+use std::marker::PhantomData;
+use std::os::raw::c_int;
+use errno::{self, Errno};
+use rusrsctp_sys::*;
+use super::{Socket, Ip, AssocId, SndInfo, PrInfo, AuthInfo, SctpFlags, MsgFlags, Callbacks};
+
+/// The style of a socket (RFC 6458 section 3 and 4), chosen by its type
+/// parameter, which decides the operations it supports
+pub trait Mode {
+    fn sock_type() -> c_int;
+    /// `snd_info` with what sockets of this mode do not take from it cleared
+    fn filter_snd_info(snd_info: SndInfo) -> SndInfo;
+}
+
+/// A one-to-one (TCP style) socket, which has at most one association.
+/// Listening sockets `accept` associations as new sockets.
+pub struct OneToOne;
+impl Mode for OneToOne {
+    fn sock_type() -> c_int { 1 } // SOCK_STREAM
+    // There is only the one association
+    fn filter_snd_info(mut snd_info: SndInfo) -> SndInfo {
+        snd_info.flags.remove(SctpFlags::SENDALL);
+        snd_info.assoc_id = 0;
+        snd_info
+    }
+}
+
+/// A one-to-many (UDP style) socket, on which any number of associations are
+/// set up implicitly by sending to, or receiving from, new peers.  Sends say
+/// which association they are for.
+pub struct OneToMany;
+impl Mode for OneToMany {
+    fn sock_type() -> c_int { 5 } // SOCK_SEQPACKET
+    // Sending to all associations is asked for with `SendTo::All`
+    fn filter_snd_info(mut snd_info: SndInfo) -> SndInfo {
+        snd_info.flags.remove(SctpFlags::SENDALL);
+        snd_info
+    }
+}
+
+/// Where a one-to-many socket sends to
+pub enum SendTo<T: Ip> {
+    /// An existing association
+    Assoc(AssocId),
+    /// A peer address (setting up an association with it, if there is none)
+    Addr(T::Addr, u16),
+    /// Every association of the socket
+    All,
+}
+
+/// Operations only a one-to-many socket supports
+impl<'a, T: 'a + Ip> Socket<'a, T, OneToMany> {
+    /// Send data.  `to` overrides the association and `SctpFlags::SENDALL` in
+    /// `snd_info`.
+    pub fn sendv(&mut self,
+                 data: &[u8],
+                 to: SendTo<T>,
+                 snd_info: Option<SndInfo>,
+                 pr_info: Option<PrInfo>,
+                 auth_info: Option<AuthInfo>,
+                 flags: MsgFlags) -> Result<usize, Errno>
+    {
+        let mut snd_info = OneToMany::filter_snd_info(snd_info.unwrap_or_default());
+        let addr = match to {
+            SendTo::Assoc(assoc_id) => {
+                snd_info.assoc_id = assoc_id;
+                None
+            },
+            SendTo::Addr(addr, port) => {
+                // the address picks the association
+                snd_info.assoc_id = 0;
+                Some((addr, port))
+            },
+            SendTo::All => {
+                snd_info.flags.insert(SctpFlags::SENDALL);
+                None
+            },
+        };
+        self.sendv_inner(data, addr, Some(snd_info), pr_info, auth_info, flags)
+    }
+
+    /// Branch an association off into a one-to-one socket of its own
+    pub fn peeloff(&mut self, assoc_id: AssocId) -> Result<Socket<'a, T, OneToOne>, Errno> {
+        let so = unsafe {
+            usrsctp_peeloff(self.inner, assoc_id)
+        };
+        if so.is_null() {
+            Err(errno::errno())
+        } else {
+            if let Some(ref callbacks) = self.callbacks {
+                unsafe {
+                    usrsctp_set_ulpinfo(so, Callbacks::ulp_info(callbacks));
+                }
+            }
+            Ok(Socket {
+                inner: so,
+                callbacks: self.callbacks.clone(),
+                readiness: None,
+                _ip: PhantomData,
+                _mode: PhantomData,
+            })
+        }
+    }
+}
@@ -0,0 +1,283 @@
+
+use std::mem;
+use std::ptr;
+use std::net::SocketAddr;
+use rusrsctp_sys::*;
+use super::{AssocId, StreamResetFlags, socket_addr_from_storage};
+
+/// Notification types which can be subscribed to with `Socket::subscribe`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EventType {
+    AssocChange,
+    PeerAddrChange,
+    RemoteError,
+    SendFailed,
+    Shutdown,
+    AdaptationIndication,
+    PartialDelivery,
+    Authentication,
+    StreamReset,
+    SenderDry,
+    NotificationsStopped,
+    AssocReset,
+    StreamChange,
+    SendFailedEvent,
+}
+
+impl EventType {
+    pub fn c_type(&self) -> u16 {
+        (match *self {
+            EventType::AssocChange => SCTP_ASSOC_CHANGE,
+            EventType::PeerAddrChange => SCTP_PEER_ADDR_CHANGE,
+            EventType::RemoteError => SCTP_REMOTE_ERROR,
+            EventType::SendFailed => SCTP_SEND_FAILED,
+            EventType::Shutdown => SCTP_SHUTDOWN_EVENT,
+            EventType::AdaptationIndication => SCTP_ADAPTATION_INDICATION,
+            EventType::PartialDelivery => SCTP_PARTIAL_DELIVERY_EVENT,
+            EventType::Authentication => SCTP_AUTHENTICATION_EVENT,
+            EventType::StreamReset => SCTP_STREAM_RESET_EVENT,
+            EventType::SenderDry => SCTP_SENDER_DRY_EVENT,
+            EventType::NotificationsStopped => SCTP_NOTIFICATIONS_STOPPED_EVENT,
+            EventType::AssocReset => SCTP_ASSOC_RESET_EVENT,
+            EventType::StreamChange => SCTP_STREAM_CHANGE_EVENT,
+            EventType::SendFailedEvent => SCTP_SEND_FAILED_EVENT,
+        }) as u16
+    }
+}
+
+/// A notification, as received via `recvv` when `MsgFlags::NOTIFICATION` is set.
+#[derive(Debug, Clone)]
+pub enum Notification {
+    /// An association came up or went down (RFC 6458 section 6.1.1)
+    AssocChange(AssocChangeEvent),
+    /// A peer address changed state (RFC 6458 section 6.1.2)
+    PeerAddrChange(PeerAddrChangeEvent),
+    /// A partial delivery was aborted (RFC 6458 section 6.1.7)
+    PartialDelivery(PartialDeliveryEvent),
+    /// A shared key event (RFC 4895)
+    Authentication(AuthKeyEvent),
+    /// Streams were reset (RFC 6525)
+    StreamReset(StreamResetEvent),
+    /// A notification which is not (yet) decoded by this library.  The value is
+    /// the notification type.
+    Other(u16),
+}
+
+impl Notification {
+    /// Decode a notification from the data returned by `recvv`.  Returns None
+    /// if the data is too short to be the notification it claims to be.
+    pub fn parse(data: &[u8]) -> Option<Notification> {
+        let header: sctp_tlv = read(data)?;
+        Some(match header.sn_type as u32 {
+            SCTP_ASSOC_CHANGE => {
+                let ev: sctp_assoc_change = read(data)?;
+                Notification::AssocChange(AssocChangeEvent {
+                    state: AssocChangeState::from_c(ev.sac_state),
+                    error: ev.sac_error,
+                    outbound_streams: ev.sac_outbound_streams,
+                    inbound_streams: ev.sac_inbound_streams,
+                    assoc_id: ev.sac_assoc_id,
+                })
+            },
+            SCTP_PEER_ADDR_CHANGE => {
+                let ev: sctp_paddr_change = read(data)?;
+                Notification::PeerAddrChange(PeerAddrChangeEvent {
+                    addr: socket_addr_from_storage(&ev.spc_aaddr),
+                    state: PeerAddrState::from_c(ev.spc_state),
+                    error: ev.spc_error,
+                    assoc_id: ev.spc_assoc_id,
+                })
+            },
+            SCTP_PARTIAL_DELIVERY_EVENT => {
+                let ev: sctp_pdapi_event = read(data)?;
+                Notification::PartialDelivery(PartialDeliveryEvent {
+                    indication: PdIndication::from_c(ev.pdapi_indication),
+                    sid: ev.pdapi_stream as u16,
+                    seq: ev.pdapi_seq,
+                    assoc_id: ev.pdapi_assoc_id,
+                })
+            },
+            SCTP_AUTHENTICATION_EVENT => {
+                let ev: sctp_authkey_event = read(data)?;
+                Notification::Authentication(AuthKeyEvent {
+                    keynumber: ev.auth_keynumber,
+                    indication: AuthIndication::from_c(ev.auth_indication),
+                    assoc_id: ev.auth_assoc_id,
+                })
+            },
+            SCTP_STREAM_RESET_EVENT => {
+                let ev: sctp_stream_reset_event = read(data)?;
+                let start = mem::size_of::<sctp_stream_reset_event>();
+                let end = ev.strreset_length as usize;
+                if end < start || end > data.len() {
+                    return None;
+                }
+                Notification::StreamReset(StreamResetEvent {
+                    flags: StreamResetFlags::from_bits_truncate(ev.strreset_flags),
+                    streams: data[start..end].chunks(2)
+                        .filter(|c| c.len() == 2)
+                        .map(|c| u16::from_ne_bytes([c[0], c[1]]))
+                        .collect(),
+                    assoc_id: ev.strreset_assoc_id,
+                })
+            },
+            other => Notification::Other(other as u16),
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AssocChangeState {
+    /// The association is ready to send and receive data
+    CommUp,
+    /// The association failed
+    CommLost,
+    /// The peer restarted the association
+    Restart,
+    /// The association was gracefully shut down
+    ShutdownComplete,
+    /// The association could not be set up
+    CantStartAssoc,
+    Unknown(u16),
+}
+
+impl AssocChangeState {
+    fn from_c(state: u16) -> AssocChangeState {
+        match state as u32 {
+            SCTP_COMM_UP => AssocChangeState::CommUp,
+            SCTP_COMM_LOST => AssocChangeState::CommLost,
+            SCTP_RESTART => AssocChangeState::Restart,
+            SCTP_SHUTDOWN_COMP => AssocChangeState::ShutdownComplete,
+            SCTP_CANT_STR_ASSOC => AssocChangeState::CantStartAssoc,
+            _ => AssocChangeState::Unknown(state),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct AssocChangeEvent {
+    pub state: AssocChangeState,
+    /// An error code, if the change was caused by an error
+    pub error: u16,
+    /// Number of streams we may send on
+    pub outbound_streams: u16,
+    /// Number of streams the peer may send on
+    pub inbound_streams: u16,
+    pub assoc_id: AssocId,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PeerAddrState {
+    /// The address is reachable again
+    Available,
+    /// The address can not be reached
+    Unreachable,
+    /// The address is no longer part of the association
+    Removed,
+    /// The address is now part of the association
+    Added,
+    /// The address is now the primary destination
+    MadePrimary,
+    /// The address has been confirmed as valid
+    Confirmed,
+    Unknown(u32),
+}
+
+impl PeerAddrState {
+    fn from_c(state: u32) -> PeerAddrState {
+        match state {
+            SCTP_ADDR_AVAILABLE => PeerAddrState::Available,
+            SCTP_ADDR_UNREACHABLE => PeerAddrState::Unreachable,
+            SCTP_ADDR_REMOVED => PeerAddrState::Removed,
+            SCTP_ADDR_ADDED => PeerAddrState::Added,
+            SCTP_ADDR_MADE_PRIM => PeerAddrState::MadePrimary,
+            SCTP_ADDR_CONFIRMED => PeerAddrState::Confirmed,
+            other => PeerAddrState::Unknown(other),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct PeerAddrChangeEvent {
+    /// The peer address affected (None if not IPv4 or IPv6)
+    pub addr: Option<SocketAddr>,
+    pub state: PeerAddrState,
+    /// An error code, if the state change was caused by an error
+    pub error: u32,
+    pub assoc_id: AssocId,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PdIndication {
+    /// The rest of the partially delivered message will never arrive
+    Aborted,
+    Unknown(u32),
+}
+
+impl PdIndication {
+    fn from_c(indication: u32) -> PdIndication {
+        match indication {
+            SCTP_PARTIAL_DELIVERY_ABORTED => PdIndication::Aborted,
+            other => PdIndication::Unknown(other),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct PartialDeliveryEvent {
+    pub indication: PdIndication,
+    /// Stream of the message which was being delivered
+    pub sid: u16,
+    /// Stream sequence number of the message.  If I-DATA (RFC 8260) is in use,
+    /// this is only the lower 16 bits of the message identifier (MID), as usrsctp
+    /// fills no more.
+    pub seq: u32,
+    pub assoc_id: AssocId,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AuthIndication {
+    /// The key is now the active key of the association
+    NewKey,
+    /// The peer does not support SCTP-AUTH
+    NoAuth,
+    /// The key is no longer used by the association and may be deleted
+    FreeKey,
+    Unknown(u32),
+}
+
+impl AuthIndication {
+    fn from_c(indication: u32) -> AuthIndication {
+        match indication {
+            SCTP_AUTH_NEW_KEY => AuthIndication::NewKey,
+            SCTP_AUTH_NO_AUTH => AuthIndication::NoAuth,
+            SCTP_AUTH_FREE_KEY => AuthIndication::FreeKey,
+            other => AuthIndication::Unknown(other),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct AuthKeyEvent {
+    /// The shared key identifier the event refers to
+    pub keynumber: u16,
+    pub indication: AuthIndication,
+    pub assoc_id: AssocId,
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamResetEvent {
+    pub flags: StreamResetFlags,
+    /// The streams which were reset (empty if all of them were)
+    pub streams: Vec<u16>,
+    pub assoc_id: AssocId,
+}
+
+// Notifications arrive in a byte buffer with no alignment guarantee
+fn read<S>(data: &[u8]) -> Option<S> {
+    if data.len() < mem::size_of::<S>() {
+        None
+    } else {
+        Some(unsafe { ptr::read_unaligned(data.as_ptr() as *const S) })
+    }
+}
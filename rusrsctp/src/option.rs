@@ -1,5 +1,7 @@
 
 use std::mem;
+use std::slice;
+use std::os::raw::c_void;
 use rusrsctp_sys::*;
 
 pub trait SctpOption {
@@ -12,26 +14,146 @@ pub trait SctpOption {
     }
 }
 
-pub struct RtoInfo(pub sctp_rtoinfo);
-impl SctpOption for RtoInfo {
-    type Value = sctp_rtoinfo;
-    fn c_name(&self) -> i32 { SCTP_RTOINFO as i32 }
-    fn value_ptr<'a>(&'a self) -> &'a Self::Value {
-        &self.0
-    }
-    fn value_ptr_mut<'a>(&'a mut self) -> &'a mut Self::Value {
-        &mut self.0
+// Define a newtype option wrapping the raw usrsctp value struct
+macro_rules! sctp_option {
+    ($name:ident, $value:ty, $c_name:expr) => {
+        pub struct $name(pub $value);
+        impl SctpOption for $name {
+            type Value = $value;
+            fn c_name(&self) -> i32 { $c_name as i32 }
+            fn value_ptr<'a>(&'a self) -> &'a Self::Value {
+                &self.0
+            }
+            fn value_ptr_mut<'a>(&'a mut self) -> &'a mut Self::Value {
+                &mut self.0
+            }
+        }
     }
 }
 
-pub struct RemoteUdpEncapsPort(pub sctp_udpencaps);
-impl SctpOption for RemoteUdpEncapsPort {
-    type Value = sctp_udpencaps;
-    fn c_name(&self) -> i32 { SCTP_REMOTE_UDP_ENCAPS_PORT as i32 }
-    fn value_ptr<'a>(&'a self) -> &'a Self::Value {
-        &self.0
+sctp_option!(RtoInfo, sctp_rtoinfo, SCTP_RTOINFO);
+/// Parameters for the INITs of new associations (see `InitParams`)
+sctp_option!(InitMsg, sctp_initmsg, SCTP_INITMSG);
+/// The state and parameters of an association (read only)
+sctp_option!(Status, sctp_status, SCTP_STATUS);
+sctp_option!(RemoteUdpEncapsPort, sctp_udpencaps, SCTP_REMOTE_UDP_ENCAPS_PORT);
+
+/// Subscribe to (or unsubscribe from) a notification type
+sctp_option!(Event, sctp_event, SCTP_EVENT);
+/// Whether `recvv` should return an `RcvInfo` (nonzero) or not (zero)
+sctp_option!(RecvRcvInfo, u32, SCTP_RECVRCVINFO);
+
+/// Set the active shared key of an endpoint or association (RFC 4895)
+sctp_option!(AuthActiveKey, sctp_authkeyid, SCTP_AUTH_ACTIVE_KEY);
+/// Deactivate a shared key so that it is no longer used for sending
+sctp_option!(AuthDeactivateKey, sctp_authkeyid, SCTP_AUTH_DEACTIVATE_KEY);
+/// Delete a (deactivated) shared key
+sctp_option!(AuthDeleteKey, sctp_authkeyid, SCTP_AUTH_DELETE_KEY);
+/// Request that a chunk type be authenticated by the peer
+sctp_option!(AuthChunk, sctp_authchunk, SCTP_AUTH_CHUNK);
+/// Enable or disable SCTP-AUTH on the endpoint (nonzero = enabled)
+sctp_option!(AuthSupported, sctp_assoc_value, SCTP_AUTH_SUPPORTED);
+
+/// Select the stream scheduler (see `StreamScheduler`)
+sctp_option!(PluggableSs, sctp_assoc_value, SCTP_PLUGGABLE_SS);
+/// Set the scheduler specific value (priority or weight) of a stream
+sctp_option!(SsValue, sctp_stream_value, SCTP_SS_VALUE);
+
+/// Select the congestion control module (see `CongestionControl`)
+sctp_option!(PluggableCc, sctp_assoc_value, SCTP_PLUGGABLE_CC);
+/// Set a congestion control module parameter (see `CcParameter`)
+sctp_option!(CcOption, sctp_cc_option, SCTP_CC_OPTION);
+
+/// Whether (and how) partial deliveries from different messages may interleave:
+/// 0 = not at all, 1 = between associations, 2 = between streams too
+sctp_option!(FragmentInterleave, u32, SCTP_FRAGMENT_INTERLEAVE);
+/// Enable I-DATA message interleaving (RFC 8260).  On an association this reads
+/// back whether it was negotiated with the peer.
+sctp_option!(InterleavingSupported, sctp_assoc_value, SCTP_INTERLEAVING_SUPPORTED);
+
+/// Enable dynamic address reconfiguration (ASCONF, RFC 5061) on the endpoint
+sctp_option!(AsconfSupported, sctp_assoc_value, SCTP_ASCONF_SUPPORTED);
+/// Whether addresses added to or removed from the host (for wildcard bound
+/// sockets) are automatically announced to peers (nonzero = yes)
+sctp_option!(AutoAsconf, u32, SCTP_AUTO_ASCONF);
+/// Ask the peer to use one of our addresses as its primary destination
+sctp_option!(SetPeerPrimaryAddr, sctp_setpeerprim, SCTP_SET_PEER_PRIMARY_ADDR);
+
+/// Whether messages end only where a send says so with `SctpFlags::EOR`
+/// (nonzero = yes), allowing messages larger than the send buffer
+sctp_option!(ExplicitEor, u32, SCTP_EXPLICIT_EOR);
+
+/// Which stream reset requests (RFC 6525) the endpoint accepts, as
+/// `SCTP_ENABLE_*` bits
+sctp_option!(EnableStreamReset, sctp_assoc_value, SCTP_ENABLE_STREAM_RESET);
+
+// Some options end in a variable length array, so they cannot be an `SctpOption`.
+// This holds the fixed part `S` followed by the variable part, and is aligned
+// well enough for any option struct.
+pub(crate) struct OptBuf {
+    buf: Vec<u64>,
+    len: usize,
+}
+
+impl OptBuf {
+    pub(crate) fn new<S>(extra: usize) -> OptBuf {
+        let len = mem::size_of::<S>() + extra;
+        OptBuf {
+            buf: vec![0; (len + 7) / 8],
+            len: len,
+        }
     }
-    fn value_ptr_mut<'a>(&'a mut self) -> &'a mut Self::Value {
-        &mut self.0
+
+    pub(crate) fn header<S>(&self) -> &S {
+        assert!(mem::size_of::<S>() <= self.len);
+        unsafe { &*(self.buf.as_ptr() as *const S) }
+    }
+
+    pub(crate) fn header_mut<S>(&mut self) -> &mut S {
+        assert!(mem::size_of::<S>() <= self.len);
+        unsafe { &mut *(self.buf.as_mut_ptr() as *mut S) }
+    }
+
+    /// The variable part following header `S`, as elements of type `E`
+    pub(crate) fn tail<S, E>(&self) -> &[E] {
+        let (offset, count) = self.tail_extent::<S, E>();
+        unsafe {
+            let base = (self.buf.as_ptr() as *const u8).offset(offset as isize);
+            slice::from_raw_parts(base as *const E, count)
+        }
+    }
+
+    pub(crate) fn tail_mut<S, E>(&mut self) -> &mut [E] {
+        let (offset, count) = self.tail_extent::<S, E>();
+        unsafe {
+            let base = (self.buf.as_mut_ptr() as *mut u8).offset(offset as isize);
+            slice::from_raw_parts_mut(base as *mut E, count)
+        }
+    }
+
+    fn tail_extent<S, E>(&self) -> (usize, usize) {
+        let offset = mem::size_of::<S>();
+        assert!(offset <= self.len);
+        assert_eq!(offset % mem::align_of::<E>(), 0);
+        (offset, (self.len - offset) / mem::size_of::<E>())
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const c_void {
+        self.buf.as_ptr() as *const c_void
+    }
+
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut c_void {
+        self.buf.as_mut_ptr() as *mut c_void
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Shrink to the length actually filled in by a getsockopt call
+    pub(crate) fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            self.len = len;
+        }
     }
 }
@@ -0,0 +1,88 @@
+
+use std::fmt;
+use libc;
+use super::UsrSctp;
+
+/// Which raw SCTP sockets this process may open.  Running SCTP over IP directly
+/// (rather than over UDP) needs them, which usually means CAP_NET_RAW.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RawIpSupport {
+    pub ipv4: bool,
+    pub ipv6: bool,
+}
+
+impl RawIpSupport {
+    /// Whether SCTP can run over IP directly at all
+    pub fn any(&self) -> bool {
+        self.ipv4 || self.ipv6
+    }
+}
+
+/// What to do when SCTP over IP is asked for but raw sockets are unavailable
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RawIpPolicy {
+    /// Fail with `RawIpUnavailable`
+    Require,
+    /// Run over UDP on this port instead
+    FallbackToUdp(u16),
+}
+
+/// SCTP over IP was asked for, but no raw SCTP socket could be opened
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RawIpUnavailable {
+    pub support: RawIpSupport,
+}
+
+impl fmt::Display for RawIpUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SCTP over IP needs raw sockets (CAP_NET_RAW), which are unavailable \
+                   for both IPv4 and IPv6; run SCTP over UDP instead")
+    }
+}
+
+impl ::std::error::Error for RawIpUnavailable {}
+
+// Whether a raw SCTP socket of this family can be opened
+fn probe(family: libc::c_int) -> bool {
+    let fd = unsafe {
+        libc::socket(family, libc::SOCK_RAW, libc::IPPROTO_SCTP)
+    };
+    if fd < 0 {
+        false
+    } else {
+        unsafe {
+            libc::close(fd);
+        }
+        true
+    }
+}
+
+impl UsrSctp {
+    /// Check which raw SCTP sockets can be opened, without starting SCTP
+    pub fn raw_ip_support() -> RawIpSupport {
+        RawIpSupport {
+            ipv4: probe(libc::AF_INET),
+            ipv6: probe(libc::AF_INET6),
+        }
+    }
+
+    /// Initialize SCTP, like `new`, but check first that SCTP over IP (with no
+    /// `port`) can work.  If neither IPv4 nor IPv6 raw sockets are available,
+    /// `policy` decides whether to fail or to run over UDP instead.  If only one
+    /// of them is available, SCTP runs over IP for that family alone.
+    pub fn with_policy(port: Option<u16>, inner_debug: bool, policy: RawIpPolicy)
+                       -> Result<UsrSctp, RawIpUnavailable>
+    {
+        if port.is_some() {
+            return Ok(UsrSctp::new(port, inner_debug));
+        }
+        let support = UsrSctp::raw_ip_support();
+        if support.any() {
+            return Ok(UsrSctp::new(None, inner_debug));
+        }
+        match policy {
+            RawIpPolicy::Require => Err(RawIpUnavailable { support: support }),
+            RawIpPolicy::FallbackToUdp(port) => Ok(UsrSctp::new(Some(port), inner_debug)),
+        }
+    }
+}
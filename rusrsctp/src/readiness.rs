@@ -0,0 +1,236 @@
+
+use std::collections::HashMap;
+use std::os::raw::{c_int, c_void};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::ptr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use errno::{self, Errno};
+use libc;
+use rusrsctp_sys::*;
+use super::{Socket, Ip, Mode, SocketEvents};
+
+// The eventfd itself, closed once the last Readiness handle is gone
+struct EventFd(RawFd);
+
+impl EventFd {
+    fn signal(&self) {
+        let one: u64 = 1;
+        unsafe {
+            libc::write(self.0, &one as *const u64 as *const c_void, 8);
+        }
+    }
+}
+
+impl Drop for EventFd {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.0);
+        }
+    }
+}
+
+/// A bridge from usrsctp's readiness upcalls to a Linux eventfd.
+///
+/// usrsctp sockets have no file descriptor, so they cannot join an `epoll` (or
+/// `mio`) loop directly.  Instead, attach one or more sockets to a `Readiness`
+/// with `Socket::set_readiness`.  Its eventfd becomes readable whenever any of
+/// them may have become readable, writable, or errored.  When it does, call
+/// `clear` and then check each attached socket with `Socket::events`.
+///
+/// With the `mio` feature, both `Readiness` and `Socket` are `mio::event::Source`s.
+#[derive(Clone)]
+pub struct Readiness {
+    fd: Arc<EventFd>,
+}
+
+impl Readiness {
+    pub fn new() -> Result<Readiness, Errno> {
+        let fd = unsafe {
+            libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC)
+        };
+        if fd < 0 {
+            Err(errno::errno())
+        } else {
+            Ok(Readiness {
+                fd: Arc::new(EventFd(fd)),
+            })
+        }
+    }
+
+    /// Make the eventfd readable, as an upcall would
+    pub fn signal(&self) {
+        self.fd.signal();
+    }
+
+    /// Make the eventfd no longer readable.  Returns whether it was readable.
+    /// Call this before checking the sockets' events, so that no upcall is missed
+    /// in between.
+    pub fn clear(&self) -> bool {
+        let mut count: u64 = 0;
+        let rval = unsafe {
+            libc::read(self.fd.0, &mut count as *mut u64 as *mut c_void, 8)
+        };
+        rval == 8 && count > 0
+    }
+}
+
+impl AsRawFd for Readiness {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.0
+    }
+}
+
+// The eventfd of each attached socket, by the id handed to usrsctp as the upcall
+// arg.  Each attachment holds a reference of its own, and an upcall takes another
+// while it signals, so an upcall racing with `clear_readiness` (or the socket
+// being dropped) finds either a live eventfd or nothing, never a closed one.
+static UPCALL_ARGS: Mutex<Option<HashMap<usize, Arc<EventFd>>>> = Mutex::new(None);
+// Ids are just unique numbers; 0 is never used, as it reads as a null arg
+static NEXT_UPCALL_ARG: AtomicUsize = AtomicUsize::new(1);
+
+fn register_upcall_arg(fd: &Arc<EventFd>) -> usize {
+    let arg = NEXT_UPCALL_ARG.fetch_add(1, Ordering::SeqCst);
+    let mut args = UPCALL_ARGS.lock().unwrap_or_else(|e| e.into_inner());
+    args.get_or_insert_with(HashMap::new).insert(arg, fd.clone());
+    arg
+}
+
+// Release the reference held for an upcall arg, once usrsctp no longer has it
+pub(crate) fn deregister_upcall_arg(arg: usize) {
+    let fd = {
+        let mut args = UPCALL_ARGS.lock().unwrap_or_else(|e| e.into_inner());
+        args.as_mut().and_then(|a| a.remove(&arg))
+    };
+    drop(fd); // may close the eventfd, which is best done without the lock
+}
+
+unsafe extern "C" fn upcall(_so: *mut socket, arg: *mut c_void, _flags: c_int) {
+    let fd = {
+        let args = UPCALL_ARGS.lock().unwrap_or_else(|e| e.into_inner());
+        args.as_ref().and_then(|a| a.get(&(arg as usize)).cloned())
+    };
+    if let Some(fd) = fd {
+        fd.signal();
+    }
+}
+
+impl<'a, T: 'a + Ip, M: Mode> Socket<'a, T, M> {
+    /// Signal `readiness` whenever this socket's events may have changed,
+    /// replacing any readiness bridge set before.
+    pub fn set_readiness(&mut self, readiness: &Readiness) -> Result<(), Errno> {
+        let arg = register_upcall_arg(&readiness.fd);
+        let rval = unsafe {
+            usrsctp_set_upcall(self.inner, Some(upcall), arg as *mut c_void)
+        };
+        if rval < 0 {
+            let err = errno::errno();
+            deregister_upcall_arg(arg);
+            return Err(err);
+        }
+        if let Some((_, old_arg)) = self.readiness.take() {
+            deregister_upcall_arg(old_arg);
+        }
+        self.readiness = Some((readiness.clone(), arg));
+        // The socket may already be ready, which will not cause an upcall
+        readiness.signal();
+        Ok(())
+    }
+
+    /// Stop signalling the readiness bridge set with `set_readiness`
+    pub fn clear_readiness(&mut self) -> Result<(), Errno> {
+        let rval = unsafe {
+            usrsctp_set_upcall(self.inner, None, ptr::null_mut())
+        };
+        if rval < 0 {
+            Err(errno::errno())
+        } else {
+            if let Some((_, arg)) = self.readiness.take() {
+                deregister_upcall_arg(arg);
+            }
+            Ok(())
+        }
+    }
+
+    /// The readiness bridge set with `set_readiness`, if any
+    pub fn readiness(&self) -> Option<&Readiness> {
+        self.readiness.as_ref().map(|&(ref readiness, _)| readiness)
+    }
+
+    /// What the socket is ready for right now
+    pub fn events(&self) -> Result<SocketEvents, Errno> {
+        let rval = unsafe {
+            usrsctp_get_events(self.inner)
+        };
+        if rval < 0 {
+            Err(errno::errno())
+        } else {
+            Ok(SocketEvents::from_bits_truncate(rval))
+        }
+    }
+}
+
+#[cfg(feature = "mio")]
+mod mio_source {
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+    use mio::{Interest, Registry, Token};
+    use mio::event::Source;
+    use mio::unix::SourceFd;
+    use super::Readiness;
+    use super::super::{Socket, Ip, Mode};
+
+    // The eventfd is only ever readable.  Which of the socket's own events are of
+    // interest is decided by checking `Socket::events` after it fires.
+    impl Source for Readiness {
+        fn register(&mut self, registry: &Registry, token: Token, _interests: Interest)
+                    -> io::Result<()>
+        {
+            registry.register(&mut SourceFd(&self.as_raw_fd()), token, Interest::READABLE)
+        }
+
+        fn reregister(&mut self, registry: &Registry, token: Token, _interests: Interest)
+                      -> io::Result<()>
+        {
+            registry.reregister(&mut SourceFd(&self.as_raw_fd()), token, Interest::READABLE)
+        }
+
+        fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+            registry.deregister(&mut SourceFd(&self.as_raw_fd()))
+        }
+    }
+
+    /// Registering a socket that has no readiness bridge gives it one of its own.
+    impl<'a, T: 'a + Ip, M: Mode> Source for Socket<'a, T, M> {
+        fn register(&mut self, registry: &Registry, token: Token, interests: Interest)
+                    -> io::Result<()>
+        {
+            if self.readiness.is_none() {
+                let readiness = Readiness::new()
+                    .map_err(|e| io::Error::from_raw_os_error(e.0))?;
+                self.set_readiness(&readiness)
+                    .map_err(|e| io::Error::from_raw_os_error(e.0))?;
+            }
+            match self.readiness {
+                Some((ref mut readiness, _)) => readiness.register(registry, token, interests),
+                None => unreachable!(),
+            }
+        }
+
+        fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest)
+                      -> io::Result<()>
+        {
+            match self.readiness {
+                Some((ref mut readiness, _)) => readiness.reregister(registry, token, interests),
+                None => Err(io::Error::new(io::ErrorKind::NotFound, "socket not registered")),
+            }
+        }
+
+        fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+            match self.readiness {
+                Some((ref mut readiness, _)) => readiness.deregister(registry),
+                None => Err(io::Error::new(io::ErrorKind::NotFound, "socket not registered")),
+            }
+        }
+    }
+}
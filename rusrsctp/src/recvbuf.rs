@@ -0,0 +1,78 @@
+
+use std::fmt;
+use std::ops::Deref;
+use std::slice;
+use libc;
+
+/// Received data, in the buffer usrsctp received it into.
+///
+/// Handed out by sockets from `UsrSctp::socket_with_buffer_callbacks`, so that
+/// received data need not be copied.  The buffer is freed when this is dropped.
+/// With the `bytes` feature, it converts into a `bytes::Bytes` without copying.
+pub struct RecvBuf {
+    ptr: *mut u8,
+    len: usize,
+}
+
+// The buffer is plain malloc()ed memory, owned by nothing but us
+unsafe impl Send for RecvBuf {}
+unsafe impl Sync for RecvBuf {}
+
+impl RecvBuf {
+    // Take ownership of a buffer from usrsctp (ptr may be null, if len is 0)
+    pub(crate) unsafe fn from_raw(ptr: *mut u8, len: usize) -> RecvBuf {
+        RecvBuf {
+            ptr: ptr,
+            len: if ptr.is_null() { 0 } else { len },
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Deref for RecvBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        if self.ptr.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.ptr, self.len) }
+        }
+    }
+}
+
+impl AsRef<[u8]> for RecvBuf {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl fmt::Debug for RecvBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RecvBuf").field("len", &self.len).finish()
+    }
+}
+
+impl Drop for RecvBuf {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                libc::free(self.ptr as *mut libc::c_void);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl From<RecvBuf> for ::bytes::Bytes {
+    fn from(buf: RecvBuf) -> ::bytes::Bytes {
+        ::bytes::Bytes::from_owner(buf)
+    }
+}
@@ -0,0 +1,41 @@
+
+use errno::Errno;
+use libc;
+use rusrsctp_sys::*;
+use super::{Socket, Ip, Mode, AssocId, StreamResetFlags, EnableStreamReset, OptBuf};
+
+/// Stream reconfiguration (RFC 6525).
+///
+/// Resetting a stream restarts its sequence numbers, which is how applications
+/// such as WebRTC data channels close a stream without closing the association.
+/// Subscribe to `EventType::StreamReset` to learn of resets by either side.
+impl<'a, T: 'a + Ip, M: Mode> Socket<'a, T, M> {
+    /// Accept (or refuse) stream reset requests from the peer.  Use
+    /// SCTP_FUTURE_ASSOC to set it for associations created afterwards.
+    pub fn enable_stream_reset(&mut self, assoc_id: AssocId, on: bool) -> Result<(), Errno> {
+        self.setsockopt(EnableStreamReset(sctp_assoc_value {
+            assoc_id: assoc_id,
+            assoc_value: if on { SCTP_ENABLE_RESET_STREAM_REQ } else { 0 },
+        }))
+    }
+
+    /// Reset streams of an association, in the directions given by `flags`
+    /// (`INCOMING`, `OUTGOING`, or both).  An empty list resets all streams.
+    pub fn reset_streams(&mut self, assoc_id: AssocId, streams: &[u16], flags: StreamResetFlags)
+                         -> Result<(), Errno>
+    {
+        if streams.len() > u16::max_value() as usize {
+            return Err(Errno(libc::EINVAL));
+        }
+        let mut buf = OptBuf::new::<sctp_reset_streams>(streams.len() * 2);
+        {
+            let header = buf.header_mut::<sctp_reset_streams>();
+            header.srs_assoc_id = assoc_id;
+            header.srs_flags = (flags & (StreamResetFlags::INCOMING | StreamResetFlags::OUTGOING))
+                .bits();
+            header.srs_number_streams = streams.len() as u16;
+        }
+        buf.tail_mut::<sctp_reset_streams, u16>().copy_from_slice(streams);
+        self.setsockopt_buf(SCTP_RESET_STREAMS, &buf)
+    }
+}
@@ -0,0 +1,101 @@
+
+use errno::Errno;
+use rusrsctp_sys::*;
+use super::{Socket, Ip, Mode, AssocId, PluggableSs, SsValue};
+
+/// Stream schedulers, which decide which stream's queued messages are sent next
+/// when several streams have data waiting.
+///
+/// usrsctp has no separate weighted fair queueing scheduler; `FairBandwidth` is
+/// the closest one it offers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StreamScheduler {
+    /// The usrsctp default (round robin, without the fairness bookkeeping)
+    Default,
+    /// Serve each stream in turn, one message at a time
+    RoundRobin,
+    /// Serve each stream in turn, one packet at a time
+    RoundRobinPacket,
+    /// Serve streams by their stream value; lower values are served first
+    Priority,
+    /// Serve streams so that each gets an equal share of the bandwidth
+    FairBandwidth,
+    /// Send messages in the order they were queued, regardless of stream
+    FirstComeFirstServed,
+}
+
+impl StreamScheduler {
+    pub fn c_value(&self) -> u32 {
+        match *self {
+            StreamScheduler::Default => SCTP_SS_DEFAULT,
+            StreamScheduler::RoundRobin => SCTP_SS_ROUND_ROBIN,
+            StreamScheduler::RoundRobinPacket => SCTP_SS_ROUND_ROBIN_PACKET,
+            StreamScheduler::Priority => SCTP_SS_PRIORITY,
+            StreamScheduler::FairBandwidth => SCTP_SS_FAIR_BANDWITH,
+            StreamScheduler::FirstComeFirstServed => SCTP_SS_FIRST_COME,
+        }
+    }
+
+    pub fn from_c_value(value: u32) -> Option<StreamScheduler> {
+        match value {
+            SCTP_SS_DEFAULT => Some(StreamScheduler::Default),
+            SCTP_SS_ROUND_ROBIN => Some(StreamScheduler::RoundRobin),
+            SCTP_SS_ROUND_ROBIN_PACKET => Some(StreamScheduler::RoundRobinPacket),
+            SCTP_SS_PRIORITY => Some(StreamScheduler::Priority),
+            SCTP_SS_FAIR_BANDWITH => Some(StreamScheduler::FairBandwidth),
+            SCTP_SS_FIRST_COME => Some(StreamScheduler::FirstComeFirstServed),
+            _ => None,
+        }
+    }
+}
+
+/// Stream scheduling.
+///
+/// Streams are the `sid` of `SndInfo`.  Stream values are only meaningful to the
+/// scheduler in use (the priority for `StreamScheduler::Priority`), and since
+/// streams only exist once an association does, they must be set per association.
+impl<'a, T: 'a + Ip, M: Mode> Socket<'a, T, M> {
+    /// Select the stream scheduler.  Use SCTP_FUTURE_ASSOC as `assoc_id` to set
+    /// it for associations created afterwards.
+    pub fn set_stream_scheduler(&mut self, assoc_id: AssocId, scheduler: StreamScheduler)
+                                -> Result<(), Errno>
+    {
+        self.setsockopt(PluggableSs(sctp_assoc_value {
+            assoc_id: assoc_id,
+            assoc_value: scheduler.c_value(),
+        }))
+    }
+
+    /// Get the stream scheduler in use.  Returns None if usrsctp reports a
+    /// scheduler unknown to this library.
+    pub fn get_stream_scheduler(&mut self, assoc_id: AssocId)
+                                -> Result<Option<StreamScheduler>, Errno>
+    {
+        let ss = self.getsockopt(PluggableSs(sctp_assoc_value {
+            assoc_id: assoc_id,
+            assoc_value: 0,
+        }))?;
+        Ok(StreamScheduler::from_c_value(ss.0.assoc_value))
+    }
+
+    /// Set the priority or weight of stream `sid` on an association
+    pub fn set_stream_value(&mut self, assoc_id: AssocId, sid: u16, value: u16)
+                            -> Result<(), Errno>
+    {
+        self.setsockopt(SsValue(sctp_stream_value {
+            assoc_id: assoc_id,
+            stream_id: sid,
+            stream_value: value,
+        }))
+    }
+
+    /// Get the priority or weight of stream `sid` on an association
+    pub fn get_stream_value(&mut self, assoc_id: AssocId, sid: u16) -> Result<u16, Errno> {
+        let sv = self.getsockopt(SsValue(sctp_stream_value {
+            assoc_id: assoc_id,
+            stream_id: sid,
+            stream_value: 0,
+        }))?;
+        Ok(sv.0.stream_value)
+    }
+}
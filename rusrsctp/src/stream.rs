@@ -0,0 +1,130 @@
+
+use std::cmp;
+use std::io::{self, Read, Write, BufRead};
+use errno::Errno;
+use super::{UsrSctp, Socket, Ip, OneToOne, SndInfo, MsgFlags, Shutdown};
+
+// The most sent as one message by a single write, so that writes fit the send buffer
+const MAX_WRITE: usize = 65536;
+// The size of the read buffer
+const READ_BUF: usize = 65536;
+
+fn errno_to_io(e: Errno) -> io::Error {
+    io::Error::from_raw_os_error(e.0)
+}
+
+/// A connected one-to-one socket used as a plain reliable byte pipe, through
+/// `std::io::Read`, `BufRead` and `Write`.
+///
+/// Everything is sent on one stream with one PPID.  Message boundaries are not
+/// preserved: each `write` may become a message (or be split into several), and
+/// reads run on across the ends of received messages, just as with TCP.  Any
+/// notifications received are skipped.
+pub struct SctpStream<'a, T: 'a + Ip> {
+    socket: Socket<'a, T>,
+    snd_info: SndInfo,
+    buf: Vec<u8>,
+    pos: usize,
+    len: usize,
+}
+
+impl<'a, T: 'a + Ip> SctpStream<'a, T> {
+    /// Connect to a server, sending on stream 0 with PPID 0
+    pub fn connect(sctp: &'a UsrSctp, addr: T::Addr, port: u16) -> io::Result<SctpStream<'a, T>> {
+        let mut socket = sctp.socket::<T, OneToOne>().map_err(errno_to_io)?;
+        socket.connect(addr, port).map_err(errno_to_io)?;
+        Ok(SctpStream::new(socket, 0, 0))
+    }
+
+    /// Use a connected (or accepted) one-to-one socket, sending on stream `sid`
+    /// with PPID `ppid`.  Received data is read whatever its stream and PPID.
+    pub fn new(socket: Socket<'a, T>, sid: u16, ppid: u32) -> SctpStream<'a, T> {
+        SctpStream {
+            socket: socket,
+            snd_info: SndInfo {
+                sid: sid,
+                ppid: ppid,
+                ..Default::default()
+            },
+            buf: vec![0; READ_BUF],
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &Socket<'a, T> {
+        &self.socket
+    }
+
+    pub fn get_mut(&mut self) -> &mut Socket<'a, T> {
+        &mut self.socket
+    }
+
+    /// Get the socket back.  Any buffered data not yet read is lost.
+    pub fn into_inner(self) -> Socket<'a, T> {
+        self.socket
+    }
+
+    /// Shut down reading, writing, or both
+    pub fn shutdown(&mut self, how: Shutdown) -> io::Result<()> {
+        self.socket.shutdown(how).map_err(errno_to_io)
+    }
+}
+
+impl<'a, T: 'a + Ip> Read for SctpStream<'a, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Large reads skip our buffer
+        if self.pos == self.len && buf.len() >= self.buf.len() {
+            loop {
+                let (len, _, _, flags) = self.socket.recvv(buf).map_err(errno_to_io)?;
+                if !flags.contains(MsgFlags::NOTIFICATION) {
+                    return Ok(len);
+                }
+            }
+        }
+        let n = {
+            let available = self.fill_buf()?;
+            let n = cmp::min(available.len(), buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<'a, T: 'a + Ip> BufRead for SctpStream<'a, T> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.pos == self.len {
+            let (len, _, _, flags) = self.socket.recvv(&mut self.buf).map_err(errno_to_io)?;
+            if flags.contains(MsgFlags::NOTIFICATION) {
+                continue;
+            }
+            self.pos = 0;
+            self.len = len;
+            if len == 0 {
+                break; // end of stream
+            }
+        }
+        Ok(&self.buf[self.pos..self.len])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = cmp::min(self.pos + amt, self.len);
+    }
+}
+
+impl<'a, T: 'a + Ip> Write for SctpStream<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0); // SCTP messages are never empty
+        }
+        let len = cmp::min(buf.len(), MAX_WRITE);
+        self.socket.sendv(&buf[..len], None, Some(self.snd_info), None, None, MsgFlags::empty())
+            .map_err(errno_to_io)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(()) // nothing is buffered here
+    }
+}
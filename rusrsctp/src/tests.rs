@@ -1,13 +1,20 @@
 
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use super::*;
 
+// The bytes of a notification struct, as recvv would return them
+fn notification_bytes<E>(ev: &E) -> &[u8] {
+    unsafe {
+        ::std::slice::from_raw_parts(ev as *const E as *const u8, mem::size_of::<E>())
+    }
+}
+
 #[test]
 fn setup_and_teardown4() {
     {
         let sctp = UsrSctp::new(Some(9899), true);
         {
-            let _socket = sctp.socket::<Ipv4>(false).unwrap();
+            let _socket = sctp.socket::<Ipv4, OneToOne>().unwrap();
         } // socket drops here
     } // sctp drops here
     assert!(true)
@@ -18,7 +25,7 @@ fn setup_and_teardown6() {
     {
         let sctp = UsrSctp::new(Some(9899), true);
         {
-            let _socket = sctp.socket::<Ipv6>(false).unwrap();
+            let _socket = sctp.socket::<Ipv6, OneToOne>().unwrap();
         } // socket drops here
     } // sctp drops here
     assert!(true)
@@ -30,17 +37,28 @@ fn setup_and_teardown6() {
 fn test_socket_outlive_usrsctp() {
     let socket = {
         let sctp = UsrSctp::new(Some(9899), true);
-        sctp.socket::<Ipv4>(false).unwrap()
+        sctp.socket::<Ipv4, OneToOne>().unwrap()
     };
     assert!(true)
 }
  */
 
+#[test]
+fn raw_ip_policy() {
+    let support = UsrSctp::raw_ip_support();
+    // with a port, raw sockets are not needed
+    let _sctp = UsrSctp::with_policy(Some(9899), true, RawIpPolicy::Require).unwrap();
+    if !support.any() {
+        let err = UsrSctp::with_policy(None, true, RawIpPolicy::Require).err().unwrap();
+        assert!(err.to_string().contains("CAP_NET_RAW"));
+    }
+}
+
 #[test]
 fn bind4() {
     let sctp = UsrSctp::new(Some(9899), true);
     {
-        let mut socket = sctp.socket::<Ipv4>(false).unwrap();
+        let mut socket = sctp.socket::<Ipv4, OneToOne>().unwrap();
         socket.bind(Ipv4Addr::new(0, 0, 0, 0), 0).unwrap(); // wildcard addr and port
     }
 }
@@ -49,7 +67,7 @@ fn bind4() {
 fn bind6() {
     let sctp = UsrSctp::new(Some(9899), true);
     {
-        let mut socket = sctp.socket::<Ipv6>(false).unwrap();
+        let mut socket = sctp.socket::<Ipv6, OneToOne>().unwrap();
         socket.bind(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 0).unwrap(); // wildcard addr and port
     }
 }
@@ -58,7 +76,7 @@ fn bind6() {
 fn listen4() {
     let sctp = UsrSctp::new(Some(9899), true);
     {
-        let mut socket = sctp.socket::<Ipv4>(true).unwrap();
+        let mut socket = sctp.socket::<Ipv4, OneToMany>().unwrap();
         socket.bind(Ipv4Addr::new(0, 0, 0, 0), 0).unwrap(); // wildcard addr and port
         socket.listen(8).unwrap();
     }
@@ -69,7 +87,7 @@ fn listen4() {
 fn listen6() {
     let sctp = UsrSctp::new(Some(9899), true);
     {
-        let mut socket = sctp.socket::<Ipv6>(true).unwrap();
+        let mut socket = sctp.socket::<Ipv6, OneToMany>().unwrap();
         socket.bind(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 0).unwrap(); // wildcard addr and port
         socket.listen(8).unwrap();
     }
@@ -79,7 +97,7 @@ fn listen6() {
 fn accept4() {
     let sctp = UsrSctp::new(Some(9899), true);
     {
-        let mut socket = sctp.socket::<Ipv4>(false).unwrap();
+        let mut socket = sctp.socket::<Ipv4, OneToOne>().unwrap();
         socket.set_non_blocking(true).unwrap();
         socket.bind(Ipv4Addr::new(0, 0, 0, 0), 0).unwrap(); // wildcard addr and port
         socket.listen(8).unwrap();
@@ -97,7 +115,7 @@ fn accept4() {
 fn accept6() {
     let sctp = UsrSctp::new(Some(9899), true);
     {
-        let mut socket = sctp.socket::<Ipv6>(false).unwrap();
+        let mut socket = sctp.socket::<Ipv6, OneToOne>().unwrap();
         socket.set_non_blocking(true).unwrap();
         socket.bind(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 0).unwrap(); // wildcard addr and port
         socket.listen(8).unwrap();
@@ -115,7 +133,7 @@ fn accept6() {
 fn connect4() {
     let sctp = UsrSctp::new(Some(9899), true);
     {
-        let mut socket = sctp.socket::<Ipv4>(false).unwrap();
+        let mut socket = sctp.socket::<Ipv4, OneToOne>().unwrap();
         socket.set_non_blocking(true).unwrap();
         match socket.connect(Ipv4Addr::new(127, 0, 0, 1), 10000) {
             Ok(_) => (), // unlikely, but not a failure
@@ -131,7 +149,7 @@ fn connect4() {
 fn connect6() {
     let sctp = UsrSctp::new(Some(9899), true);
     {
-        let mut socket = sctp.socket::<Ipv6>(false).unwrap();
+        let mut socket = sctp.socket::<Ipv6, OneToOne>().unwrap();
         socket.set_non_blocking(true).unwrap();
         match socket.connect(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1), 10000) {
             Ok(_) => (), // unlikely, but not a failure
@@ -143,11 +161,182 @@ fn connect6() {
     }
 }
 
+#[test]
+fn one_to_many_send() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    let mut server = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    server.bind(Ipv4Addr::new(127, 0, 0, 1), 5006).unwrap();
+    server.listen(8).unwrap();
+    let mut client = sctp.socket::<Ipv4, OneToMany>().unwrap();
+    // sending to a new peer sets up the association implicitly
+    client.sendv(b"implicit", SendTo::Addr(Ipv4Addr::new(127, 0, 0, 1), 5006),
+                 None, None, None, MsgFlags::empty()).unwrap();
+    let (_addr, _port, mut conn) = server.accept().unwrap();
+    let mut buf = [0u8; 16];
+    let (len, _from, _rcv_info, _flags) = conn.recvv(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"implicit");
+
+    // An association id left over in the send info does not get in the way:
+    // the address picks the association, and one-to-one sockets have only one
+    let stale = SndInfo { assoc_id: 12345, ..Default::default() };
+    client.sendv(b"by addr", SendTo::Addr(Ipv4Addr::new(127, 0, 0, 1), 5006),
+                 Some(stale), None, None, MsgFlags::empty()).unwrap();
+    let (len, _from, _rcv_info, _flags) = conn.recvv(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"by addr");
+    conn.sendv(b"reply", None, Some(stale), None, None, MsgFlags::empty()).unwrap();
+    let (len, _from, _rcv_info, _flags) = client.recvv(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"reply");
+}
+
+#[test]
+fn accept_peer_address() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    let mut server = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    server.bind(Ipv4Addr::new(127, 0, 0, 1), 5017).unwrap();
+    server.listen(8).unwrap();
+    let mut client = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    client.bind(Ipv4Addr::new(127, 0, 0, 1), 5018).unwrap();
+    client.connect(Ipv4Addr::new(127, 0, 0, 1), 5017).unwrap();
+    // accept must be given room for the address, or it returns none
+    let (addr, port, _conn) = server.accept().unwrap();
+    assert_eq!((addr, port), (Ipv4Addr::new(127, 0, 0, 1), 5018));
+}
+
+#[test]
+fn dual_stack() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    let mut server = sctp.socket::<DualStack, OneToOne>().unwrap();
+    server.bind(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 5008).unwrap();
+    server.listen(8).unwrap();
+
+    // an IPv4 client comes back unmapped
+    let mut client4 = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    client4.connect(Ipv4Addr::new(127, 0, 0, 1), 5008).unwrap();
+    let (addr, _port, _conn) = server.accept().unwrap();
+    assert_eq!(addr, IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)));
+
+    let mut client6 = sctp.socket::<Ipv6, OneToOne>().unwrap();
+    client6.bind(Ipv6Addr::LOCALHOST, 5019).unwrap();
+    client6.connect(Ipv6Addr::LOCALHOST, 5008).unwrap();
+    let (addr, mut conn) = server.accept_from().unwrap();
+    assert_eq!(addr, "[::1]:5019".parse().unwrap());
+    client6.sendv(b"dual", None, None, None, None, MsgFlags::empty()).unwrap();
+    let mut buf = [0u8; 16];
+    let (len, from, _rcv_info, _flags) = conn.recv_from(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"dual");
+    assert!(from.map(|from| from.is_ipv6()).unwrap_or(true));
+
+    // and addresses of both families may be given at once
+    let mut client = sctp.socket::<DualStack, OneToOne>().unwrap();
+    client.connectx(&[(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 5008),
+                      (IpAddr::V6(Ipv6Addr::LOCALHOST), 5008)]).unwrap();
+    let _ = server.accept().unwrap();
+}
+
+#[test]
+fn connect_completion() {
+    use std::time::Duration;
+    let sctp = UsrSctp::new(Some(9899), true);
+    let mut server = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    server.bind(Ipv4Addr::new(127, 0, 0, 1), 5009).unwrap();
+    server.listen(8).unwrap();
+
+    let mut client = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    let init = InitParams {
+        max_attempts: 3,
+        max_init_timeout: 1000,
+        ..Default::default()
+    };
+    client.connect_timeout(Ipv4Addr::new(127, 0, 0, 1), 5009, Duration::from_secs(5), Some(init))
+        .unwrap();
+    assert!(!client.get_non_blocking().unwrap());
+    let _ = server.accept().unwrap();
+
+    let mut client = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    client.set_non_blocking(true).unwrap();
+    assert_eq!(client.poll_connect().unwrap_err().0, ::libc::ENOTCONN);
+    let _ = client.connect(Ipv4Addr::new(127, 0, 0, 1), 5009); // EINPROGRESS
+    let mut up = false;
+    for _ in 0..500 {
+        if client.poll_connect().unwrap() {
+            up = true;
+            break;
+        }
+        ::std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(up);
+}
+
+#[test]
+fn connect_no_listener() {
+    use std::time::{Duration, Instant};
+    let sctp = UsrSctp::new(Some(9899), true);
+    // Nothing listens on port 5012, so the peer answers the INIT with an ABORT
+    let init = InitParams {
+        max_attempts: 2,
+        max_init_timeout: 200,
+        ..Default::default()
+    };
+    let start = Instant::now();
+    let mut client = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    let err = client.connect_timeout(Ipv4Addr::new(127, 0, 0, 1), 5012, Duration::from_secs(5),
+                                     Some(init)).unwrap_err();
+    assert_eq!(err.0, ::libc::ECONNREFUSED);
+
+    let mut socket = sctp.socket::<Ipv4, OneToMany>().unwrap();
+    let err = socket.connect_timeout(Ipv4Addr::new(127, 0, 0, 1), 5012, Duration::from_secs(5),
+                                     Some(init)).unwrap_err();
+    assert_eq!(err.0, ::libc::ECONNREFUSED);
+    // neither waited for the timeout
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn typestate_lifecycle() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    let mut listener = sctp.unbound::<Ipv4, OneToOne>().unwrap()
+        .bind(Ipv4Addr::new(127, 0, 0, 1), 5007).unwrap()
+        .listen(8).unwrap();
+    let mut client = sctp.unbound::<Ipv4, OneToOne>().unwrap()
+        .connect(Ipv4Addr::new(127, 0, 0, 1), 5007).unwrap();
+    client.sendv(b"typed", None, None, None, None, MsgFlags::empty()).unwrap();
+    let (_addr, _port, mut conn) = listener.accept().unwrap();
+    let mut buf = [0u8; 16];
+    let (len, _from, _rcv_info, _flags) = conn.recvv(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"typed");
+    // the escape hatch
+    conn.get_mut().set_non_blocking(true).unwrap();
+
+    // A failed transition hands the socket back
+    let (err, unbound) = sctp.unbound::<Ipv4, OneToOne>().unwrap()
+        .bind(Ipv4Addr::new(127, 0, 0, 1), 5007).unwrap_err();
+    assert_eq!(err.0, ::libc::EADDRINUSE);
+    let mut connecting = unbound.bind(Ipv4Addr::new(127, 0, 0, 1), 5016).unwrap()
+        .start_connect(Ipv4Addr::new(127, 0, 0, 1), 5007).unwrap();
+    let mut client = None;
+    for _ in 0..500 {
+        match connecting.poll_connect().unwrap() {
+            ConnectPoll::Up(connected) => {
+                client = Some(connected);
+                break;
+            },
+            ConnectPoll::InProgress(still) => connecting = still,
+        }
+        ::std::thread::sleep(::std::time::Duration::from_millis(10));
+    }
+    let mut client = client.expect("association did not come up");
+    client.sendv(b"polled", None, None, None, None, MsgFlags::empty()).unwrap();
+    let (_addr, port, mut conn) = listener.accept().unwrap();
+    assert_eq!(port, 5016);
+    let (len, _from, _rcv_info, _flags) = conn.recvv(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"polled");
+}
+
 #[test]
 fn shutdown() {
     let sctp = UsrSctp::new(Some(9899), true);
     {
-        let mut socket = sctp.socket::<Ipv6>(false).unwrap();
+        let mut socket = sctp.socket::<Ipv6, OneToOne>().unwrap();
         socket.set_non_blocking(true).unwrap();
         match socket.connect(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1), 10000) {
             Ok(_) => (), // unlikely, but not a failure
@@ -164,7 +353,7 @@ fn shutdown() {
 fn non_blocking() {
     let sctp = UsrSctp::new(Some(9899), true);
     {
-        let mut socket = sctp.socket::<Ipv4>(true).unwrap();
+        let mut socket = sctp.socket::<Ipv4, OneToMany>().unwrap();
         socket.set_non_blocking(true).unwrap();
         assert_eq!(socket.get_non_blocking().unwrap(), true);
         socket.set_non_blocking(false).unwrap();
@@ -175,14 +364,701 @@ fn non_blocking() {
 #[test]
 fn set_remote_encaps_port() {
     let sctp = UsrSctp::new(Some(9899), true);
-    let mut socket = sctp.socket::<Ipv4>(true).unwrap();
-    socket.setsockopt(RemoteUdpEncapsPort(sctp_udpencaps {
-        sue_address: sockaddr_storage {
-            ss_family: AF_INET as sa_family_t,
-            __ss_padding: [0; 118],
-            __ss_align: 0,
+    assert_eq!(sctp.get_udp_encaps_port(), 9899);
+    let mut socket = sctp.socket::<Ipv4, OneToMany>().unwrap();
+    socket.set_remote_encaps_port(SCTP_FUTURE_ASSOC, None, 9898).unwrap();
+    assert_eq!(socket.get_remote_encaps_port(SCTP_FUTURE_ASSOC, None).unwrap(), 9898);
+}
+
+#[test]
+fn udp_encaps_port() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    assert_eq!(sctp.get_udp_encaps_port(), 9899);
+    // Traffic flows tunnelled over the port SCTP started with
+    let mut server = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    server.bind(Ipv4Addr::new(127, 0, 0, 1), 5014).unwrap();
+    server.listen(8).unwrap();
+    let mut client = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    client.set_remote_encaps_port(SCTP_FUTURE_ASSOC, None, 9899).unwrap();
+    client.connect(Ipv4Addr::new(127, 0, 0, 1), 5014).unwrap();
+    client.sendv(b"tunnelled", None, None, None, None, MsgFlags::empty()).unwrap();
+    let (_addr, _port, mut conn) = server.accept().unwrap();
+    let mut buf = [0u8; 16];
+    let (len, _from, _rcv_info, _flags) = conn.recvv(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"tunnelled");
+}
+
+#[test]
+fn auth_keys() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    let mut socket = sctp.socket::<Ipv4, OneToMany>().unwrap();
+    socket.add_auth_key(SCTP_FUTURE_ASSOC, 1, b"shared secret").unwrap();
+    socket.set_active_key(SCTP_FUTURE_ASSOC, 1).unwrap();
+    assert_eq!(socket.get_active_key(SCTP_FUTURE_ASSOC).unwrap(), 1);
+    socket.deactivate_key(SCTP_FUTURE_ASSOC, 0).unwrap();
+    socket.delete_key(SCTP_FUTURE_ASSOC, 0).unwrap();
+}
+
+#[test]
+fn auth_supported() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    let mut socket = sctp.socket::<Ipv4, OneToMany>().unwrap();
+    socket.set_asconf_supported(false).unwrap(); // which needs AUTH
+    socket.set_auth_supported(false).unwrap();
+    assert!(!socket.get_auth_supported(SCTP_FUTURE_ASSOC).unwrap());
+    socket.set_auth_supported(true).unwrap();
+    assert!(socket.get_auth_supported(SCTP_FUTURE_ASSOC).unwrap());
+}
+
+#[test]
+fn auth_hmac_and_chunks() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    let mut socket = sctp.socket::<Ipv4, OneToMany>().unwrap();
+    socket.set_hmac_algos(&[HmacAlgo::Sha256, HmacAlgo::Sha1]).unwrap();
+    assert_eq!(socket.get_hmac_algos().unwrap(), vec![HmacAlgo::Sha256, HmacAlgo::Sha1]);
+    socket.add_auth_chunk(0).unwrap(); // DATA
+    assert!(socket.local_auth_chunks(SCTP_FUTURE_ASSOC).unwrap().contains(&0));
+}
+
+#[test]
+fn parse_auth_notification() {
+    let mut ev: sctp_authkey_event = unsafe { mem::zeroed() };
+    ev.auth_type = SCTP_AUTHENTICATION_EVENT as u16;
+    ev.auth_length = mem::size_of::<sctp_authkey_event>() as u32;
+    ev.auth_keynumber = 3;
+    ev.auth_indication = SCTP_AUTH_FREE_KEY;
+    ev.auth_assoc_id = 7;
+    let data = notification_bytes(&ev);
+    match Notification::parse(data) {
+        Some(Notification::Authentication(ev)) => {
+            assert_eq!(ev.keynumber, 3);
+            assert_eq!(ev.indication, AuthIndication::FreeKey);
+            assert_eq!(ev.assoc_id, 7);
         },
-        sue_assoc_id: 0,
-        sue_port: htons(9898)
-    })).unwrap()
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(Notification::parse(&data[..4]).is_none());
+}
+
+#[test]
+fn stream_scheduler() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    let mut socket = sctp.socket::<Ipv4, OneToMany>().unwrap();
+    socket.set_stream_scheduler(SCTP_FUTURE_ASSOC, StreamScheduler::Priority).unwrap();
+    assert_eq!(socket.get_stream_scheduler(SCTP_FUTURE_ASSOC).unwrap(),
+               Some(StreamScheduler::Priority));
+}
+
+#[test]
+fn congestion_control() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    let mut socket = sctp.socket::<Ipv4, OneToMany>().unwrap();
+    socket.set_congestion_control(SCTP_FUTURE_ASSOC, CongestionControl::HTcp).unwrap();
+    assert_eq!(socket.get_congestion_control(SCTP_FUTURE_ASSOC).unwrap(),
+               Some(CongestionControl::HTcp));
+}
+
+#[test]
+fn interleaving() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    let mut socket = sctp.socket::<Ipv4, OneToMany>().unwrap();
+    socket.enable_interleaving().unwrap();
+    assert!(socket.interleaving_negotiated(SCTP_FUTURE_ASSOC).unwrap());
+}
+
+#[test]
+fn interleaving_association() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    let mut server = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    server.enable_interleaving().unwrap();
+    server.bind(Ipv4Addr::new(127, 0, 0, 1), 5015).unwrap();
+    server.listen(8).unwrap();
+    let assoc_id = |socket: &mut Socket<Ipv4>| {
+        socket.getsockopt(Status(unsafe { mem::zeroed() })).unwrap().0.sstat_assoc_id
+    };
+
+    // A peer which does not offer I-DATA
+    let mut plain = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    plain.connect(Ipv4Addr::new(127, 0, 0, 1), 5015).unwrap();
+    let (_addr, _port, mut conn) = server.accept().unwrap();
+    let id = assoc_id(&mut conn);
+    assert!(!conn.interleaving_negotiated(id).unwrap());
+
+    // and one which does
+    let mut client = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    client.enable_interleaving().unwrap();
+    client.connect(Ipv4Addr::new(127, 0, 0, 1), 5015).unwrap();
+    let (_addr, _port, mut conn) = server.accept().unwrap();
+    let id = assoc_id(&mut conn);
+    assert!(conn.interleaving_negotiated(id).unwrap());
+    let id = assoc_id(&mut client);
+    assert!(client.interleaving_negotiated(id).unwrap());
+
+    // Messages are numbered by (the lower 16 bits of) their MID
+    let snd_info = SndInfo { sid: 1, ..Default::default() };
+    for _ in 0..3 {
+        client.sendv(b"i-data", None, Some(snd_info), None, None, MsgFlags::empty()).unwrap();
+    }
+    let mut buf = [0u8; 16];
+    for mid in 0..3 {
+        let (len, _from, rcv_info, flags) = conn.recvv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"i-data");
+        assert!(flags.contains(MsgFlags::EOR));
+        let rcv_info = rcv_info.unwrap();
+        assert_eq!((rcv_info.sid, rcv_info.ssn), (1, mid));
+    }
+}
+
+#[test]
+fn asconf() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    let mut socket = sctp.socket::<Ipv4, OneToMany>().unwrap();
+    socket.set_asconf_supported(true).unwrap();
+    assert!(socket.get_asconf_supported(SCTP_FUTURE_ASSOC).unwrap());
+    socket.set_auto_asconf(false).unwrap();
+}
+
+#[test]
+fn sockaddr_roundtrip() {
+    // sockaddrs hold addresses and ports in network byte order both ways
+    let sa = Ipv4::to_sockaddr(Ipv4Addr::new(127, 0, 0, 1), 5000);
+    assert_eq!(sa.sin_port, 5000u16.to_be());
+    assert_eq!(Ipv4::from_sockaddr(sa), (Ipv4Addr::new(127, 0, 0, 1), 5000));
+    let addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+    let sa = Ipv6::to_sockaddr(addr, 5000);
+    assert_eq!(sa.sin6_port, 5000u16.to_be());
+    assert_eq!(Ipv6::from_sockaddr(sa), (addr, 5000));
+}
+
+#[test]
+fn sockaddr_storage_roundtrip() {
+    let ss = Ipv6::to_sockaddr_storage(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1), 5000);
+    assert_eq!(socket_addr_from_storage(&ss),
+               Some("[fe80::1]:5000".parse().unwrap()));
+    let ss = Ipv4::to_sockaddr_storage(Ipv4Addr::new(10, 1, 2, 3), 9899);
+    assert_eq!(socket_addr_from_storage(&ss),
+               Some("10.1.2.3:9899".parse().unwrap()));
+    // as a dual-stack socket sees an IPv4 peer
+    let ss = Ipv6::to_sockaddr_storage(Ipv4Addr::new(10, 1, 2, 3).to_ipv6_mapped(), 9899);
+    assert_eq!(socket_addr_from_storage(&ss),
+               Some("10.1.2.3:9899".parse().unwrap()));
+}
+
+#[test]
+fn dcep_open() {
+    let config = ChannelConfig {
+        label: "chat".to_owned(),
+        protocol: "x".to_owned(),
+        ordered: false,
+        reliability: Reliability::MaxRetransmits(3),
+        priority: 512,
+    };
+    let msg = config.encode_open();
+    assert_eq!(&msg[..4], &[0x03, 0x81, 0x02, 0x00]);
+    assert_eq!(msg.len(), 12 + 4 + 1);
+    assert_eq!(ChannelConfig::decode_open(&msg), Some(config.clone()));
+    assert!(ChannelConfig::decode_open(&msg[..14]).is_none());
+    assert!(Reliability::Reliable.pr_info().is_none());
+    assert_eq!(Reliability::MaxPacketLifeTime(100).pr_info().unwrap().policy, PrPolicy::Ttl);
+}
+
+#[test]
+fn data_channels() {
+    use std::os::unix::net::UnixDatagram;
+    let sctp = UsrSctp::new(Some(9899), true);
+    let (unix_a, unix_b) = UnixDatagram::pair().unwrap();
+    let a = UnixTransport::new(&sctp, unix_a).unwrap();
+    let b = UnixTransport::new(&sctp, unix_b).unwrap();
+    let mut listener = sctp.socket::<Conn, OneToOne>().unwrap();
+    listener.bind(b.addr(), 5022).unwrap();
+    listener.listen(1).unwrap();
+    let mut client = sctp.socket::<Conn, OneToOne>().unwrap();
+    client.bind(a.addr(), 5022).unwrap();
+    client.connect(a.addr(), 5022).unwrap();
+    let (_addr, _port, server) = listener.accept().unwrap();
+    let mut client = DataChannels::new(client, DtlsRole::Client).unwrap();
+    let mut server = DataChannels::new(server, DtlsRole::Server).unwrap();
+
+    // OPEN and ACK
+    let sid = client.open(ChannelConfig::new("chat")).unwrap();
+    assert_eq!(sid, 0);
+    assert_eq!(server.recv().unwrap(), Some(DataChannelEvent::Opened(0)));
+    assert_eq!(server.channel(0).unwrap().0.label, "chat");
+    assert_eq!(client.recv().unwrap(), Some(DataChannelEvent::Acked(0)));
+    assert_eq!(client.channel(0).unwrap().1, ChannelState::Open);
+
+    // An OPEN on a stream id of the server's parity is ignored
+    let open = ChannelConfig::new("rogue").encode_open();
+    let snd_info = SndInfo { sid: 1, ppid: htonl(PPID_DCEP), ..Default::default() };
+    client.get_mut().sendv(&open, None, Some(snd_info), None, None, MsgFlags::empty()).unwrap();
+    client.send(0, b"hello", true).unwrap();
+    assert_eq!(server.recv().unwrap(), Some(DataChannelEvent::Message {
+        sid: 0, data: b"hello".to_vec(), text: true,
+    }));
+    assert!(server.channel(1).is_none());
+
+    // A reset of every stream (an empty list) closes every channel
+    let mut ev: sctp_stream_reset_event = unsafe { mem::zeroed() };
+    ev.strreset_type = SCTP_STREAM_RESET_EVENT as u16;
+    ev.strreset_flags = SCTP_STREAM_RESET_INCOMING_SSN as u16;
+    ev.strreset_length = mem::size_of::<sctp_stream_reset_event>() as u32;
+    let data = notification_bytes(&ev).to_vec();
+    server.handle(Message {
+        data: data, sid: 0, ppid: 0, flags: MsgFlags::NOTIFICATION | MsgFlags::EOR, rcv_info: None,
+    }).unwrap();
+    assert_eq!(server.poll_event(), Some(DataChannelEvent::Closed(0)));
+    // and the server closing its side closes the channel at the client too
+    assert_eq!(client.recv().unwrap(), Some(DataChannelEvent::Closed(0)));
+
+    // Channels are only opened on streams the association has
+    let streams = server.get_mut().getsockopt(Status(unsafe { mem::zeroed() })).unwrap()
+        .0.sstat_outstrms;
+    for _ in 0..streams / 2 {
+        assert!(server.open(ChannelConfig::new("more")).unwrap() < streams);
+    }
+    assert_eq!(server.open(ChannelConfig::new("one too many")).unwrap_err().0, ::libc::ENOSPC);
+}
+
+#[test]
+fn parse_stream_reset_notification() {
+    let mut ev: sctp_stream_reset_event = unsafe { mem::zeroed() };
+    ev.strreset_type = SCTP_STREAM_RESET_EVENT as u16;
+    ev.strreset_flags = SCTP_STREAM_RESET_INCOMING_SSN as u16;
+    ev.strreset_length = (mem::size_of::<sctp_stream_reset_event>() + 4) as u32;
+    ev.strreset_assoc_id = 2;
+    let mut data = notification_bytes(&ev).to_vec();
+    data.extend_from_slice(&4u16.to_ne_bytes());
+    data.extend_from_slice(&6u16.to_ne_bytes());
+    match Notification::parse(&data) {
+        Some(Notification::StreamReset(ev)) => {
+            assert_eq!(ev.flags, StreamResetFlags::INCOMING);
+            assert_eq!(ev.streams, vec![4, 6]);
+            assert_eq!(ev.assoc_id, 2);
+        },
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn callbacks() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    {
+        let mut socket = sctp.socket_with_callbacks::<Ipv4, OneToOne, _>(
+            |_data, _from, _rcv_info, _flags| (),
+            Some(SendCallback::new(1024, |_sb_free| ()))).unwrap();
+        socket.bind(Ipv4Addr::new(0, 0, 0, 0), 0).unwrap();
+        socket.listen(8).unwrap();
+    } // socket drops here, along with its callbacks
+}
+
+#[test]
+fn callback_receive() {
+    use std::sync::mpsc;
+    let sctp = UsrSctp::new(Some(9899), true);
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let mut server = sctp.socket_with_callbacks::<Ipv4, OneToOne, _>(
+        move |data, _from, _rcv_info, flags| {
+            if !flags.contains(MsgFlags::NOTIFICATION) && !data.is_empty() {
+                let _ = tx.lock().unwrap().send(data.to_vec());
+            }
+        },
+        None).unwrap();
+    server.bind(Ipv4Addr::new(127, 0, 0, 1), 5010).unwrap();
+    server.listen(8).unwrap();
+    let mut client = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    client.connect(Ipv4Addr::new(127, 0, 0, 1), 5010).unwrap();
+    client.sendv(b"first", None, None, None, None, MsgFlags::empty()).unwrap();
+    let (_addr, _port, conn) = server.accept().unwrap();
+    assert_eq!(rx.recv_timeout(::std::time::Duration::from_secs(5)).unwrap(), b"first");
+    // The accepted socket keeps the callbacks alive without the listener
+    drop(server);
+    client.sendv(b"second", None, None, None, None, MsgFlags::empty()).unwrap();
+    assert_eq!(rx.recv_timeout(::std::time::Duration::from_secs(5)).unwrap(), b"second");
+    // and once it is gone too, late data finds no callbacks
+    drop(conn);
+    let _ = client.sendv(b"late", None, None, None, None, MsgFlags::empty());
+}
+
+#[test]
+fn buffer_callbacks() {
+    use std::sync::mpsc;
+    let sctp = UsrSctp::new(Some(9899), true);
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let mut server = sctp.socket_with_buffer_callbacks::<Ipv4, OneToOne, _>(
+        move |data, _from, _rcv_info, flags| {
+            if !flags.contains(MsgFlags::NOTIFICATION) && !data.is_empty() {
+                let _ = tx.lock().unwrap().send(data);
+            }
+        },
+        None).unwrap();
+    server.bind(Ipv4Addr::new(127, 0, 0, 1), 5005).unwrap();
+    server.listen(8).unwrap();
+    let mut client = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    client.connect(Ipv4Addr::new(127, 0, 0, 1), 5005).unwrap();
+    client.sendv(b"zero-copy", None, None, None, None, MsgFlags::empty()).unwrap();
+    let (_addr, _port, _conn) = server.accept().unwrap();
+    let buf = rx.recv_timeout(::std::time::Duration::from_secs(5)).unwrap();
+    assert_eq!(&buf[..], b"zero-copy");
+}
+
+#[test]
+fn readiness() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    let readiness = Readiness::new().unwrap();
+    let mut socket = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    socket.set_readiness(&readiness).unwrap();
+    assert!(readiness.clear()); // set_readiness signals once
+    assert!(!readiness.clear());
+    socket.bind(Ipv4Addr::new(0, 0, 0, 0), 0).unwrap();
+    socket.listen(8).unwrap();
+    assert!(!socket.events().unwrap().contains(SocketEvents::READ));
+    socket.clear_readiness().unwrap();
+}
+
+#[test]
+fn byte_stream() {
+    use std::io::{BufRead, Read, Write};
+    let sctp = UsrSctp::new(Some(9899), true);
+    let mut server = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    server.bind(Ipv4Addr::new(127, 0, 0, 1), 5003).unwrap();
+    server.listen(8).unwrap();
+    ::std::thread::scope(|scope| {
+        let received = scope.spawn(move || {
+            let (_addr, _port, conn) = server.accept().unwrap();
+            let mut stream = SctpStream::new(conn, 0, 0);
+            let mut line = String::new();
+            stream.read_line(&mut line).unwrap();
+            let mut rest = Vec::new();
+            stream.read_to_end(&mut rest).unwrap();
+            (line, rest)
+        });
+        let mut client = SctpStream::<Ipv4>::connect(&sctp, Ipv4Addr::new(127, 0, 0, 1), 5003)
+            .unwrap();
+        // message boundaries do not matter
+        client.write_all(b"hello ").unwrap();
+        client.write_all(b"world\nmore").unwrap();
+        client.shutdown(Shutdown::Wr).unwrap();
+        let (line, rest) = received.join().unwrap();
+        assert_eq!(line, "hello world\n");
+        assert_eq!(rest, b"more".to_vec());
+    });
+}
+
+#[test]
+fn message_writer() {
+    use std::io::Write;
+    let sctp = UsrSctp::new(Some(9899), true);
+    let mut server = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    server.bind(Ipv4Addr::new(127, 0, 0, 1), 5004).unwrap();
+    server.listen(8).unwrap();
+    ::std::thread::scope(|scope| {
+        let received = scope.spawn(move || {
+            let (_addr, _port, mut conn) = server.accept().unwrap();
+            let mut message = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let (len, _from, _rcv_info, flags) = conn.recvv(&mut buf).unwrap();
+                message.extend_from_slice(&buf[..len]);
+                if flags.contains(MsgFlags::EOR) || len == 0 {
+                    return message;
+                }
+            }
+        });
+        let mut client = sctp.socket::<Ipv4, OneToOne>().unwrap();
+        client.connect(Ipv4Addr::new(127, 0, 0, 1), 5004).unwrap();
+        {
+            let mut writer = client.begin_message(SndInfo::default()).unwrap();
+            for _ in 0..64 {
+                writer.write_all(&[7u8; 1000]).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        assert_eq!(received.join().unwrap(), vec![7u8; 64000]);
+    });
+}
+
+#[test]
+fn message_writer_failure() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    // Nothing can be sent without an association, so finish fails
+    let mut socket = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    let writer = socket.begin_message(SndInfo::default()).unwrap();
+    assert!(writer.finish().is_err());
+    assert_eq!(socket.getsockopt(ExplicitEor(1)).unwrap().0, 0);
+    let writer = socket.begin_message(SndInfo::default()).unwrap();
+    writer.abort().unwrap();
+    assert_eq!(socket.getsockopt(ExplicitEor(1)).unwrap().0, 0);
+}
+
+#[test]
+fn message_assembler() {
+    let info = |sid: u16, ssn: u16| RcvInfo {
+        sid: sid, ssn: ssn, flags: SctpFlags::empty(), ppid: 51,
+        tsn: 0, cumtsn: 0, context: 0, assoc_id: 1,
+    };
+    let mut assembler = MessageAssembler::new(8);
+    // parts of two messages on different streams, interleaved
+    assert!(assembler.push(b"ab", Some(info(1, 0)), MsgFlags::empty()).unwrap().is_none());
+    assert!(assembler.push(b"xy", Some(info(2, 0)), MsgFlags::empty()).unwrap().is_none());
+    let msg = assembler.push(b"cd", Some(info(1, 0)), MsgFlags::EOR).unwrap().unwrap();
+    assert_eq!(msg.data, b"abcd".to_vec());
+    assert_eq!((msg.sid, msg.ppid), (1, 51));
+    assert_eq!(assembler.incomplete(), 1);
+    // the delivery of the other one is aborted
+    let mut ev: sctp_pdapi_event = unsafe { mem::zeroed() };
+    ev.pdapi_type = SCTP_PARTIAL_DELIVERY_EVENT as u16;
+    ev.pdapi_length = mem::size_of::<sctp_pdapi_event>() as u32;
+    ev.pdapi_indication = SCTP_PARTIAL_DELIVERY_ABORTED;
+    ev.pdapi_stream = 2;
+    ev.pdapi_seq = 0;
+    ev.pdapi_assoc_id = 1;
+    let data = notification_bytes(&ev);
+    assembler.push(data, None, MsgFlags::NOTIFICATION | MsgFlags::EOR).unwrap();
+    assert_eq!(assembler.incomplete(), 0);
+    // an ordered and an unordered message with the same ssn, interleaved on one
+    // stream
+    let unordered = RcvInfo { flags: SctpFlags::UNORDERED, ..info(4, 7) };
+    assert!(assembler.push(b"o", Some(info(4, 7)), MsgFlags::empty()).unwrap().is_none());
+    assert!(assembler.push(b"u", Some(unordered), MsgFlags::empty()).unwrap().is_none());
+    assert_eq!(assembler.incomplete(), 2);
+    let msg = assembler.push(b"U", Some(unordered), MsgFlags::EOR).unwrap().unwrap();
+    assert_eq!(msg.data, b"uU".to_vec());
+    let msg = assembler.push(b"O", Some(info(4, 7)), MsgFlags::EOR).unwrap().unwrap();
+    assert_eq!(msg.data, b"oO".to_vec());
+    assert_eq!(assembler.incomplete(), 0);
+    // the size cap
+    assert!(assembler.push(b"12345", Some(info(3, 0)), MsgFlags::empty()).unwrap().is_none());
+    assert!(assembler.push(b"6789", Some(info(3, 0)), MsgFlags::empty()).is_err());
+    assert!(assembler.push(b"0", Some(info(3, 0)), MsgFlags::EOR).unwrap().is_none());
+    assert_eq!(assembler.incomplete(), 0);
+    // data which can not be told apart
+    assert_eq!(assembler.push(b"?", None, MsgFlags::EOR).unwrap_err().0, ::libc::EINVAL);
+}
+
+#[test]
+fn unix_transport() {
+    use std::os::unix::net::UnixDatagram;
+    let sctp = UsrSctp::new(Some(9899), true);
+    let (unix_a, unix_b) = UnixDatagram::pair().unwrap();
+    let a = UnixTransport::new(&sctp, unix_a).unwrap();
+    let b = UnixTransport::new(&sctp, unix_b).unwrap();
+    let mut server = sctp.socket::<Conn, OneToOne>().unwrap();
+    server.bind(b.addr(), 5001).unwrap();
+    server.listen(1).unwrap();
+    let mut client = sctp.socket::<Conn, OneToOne>().unwrap();
+    client.bind(a.addr(), 5001).unwrap();
+    client.connect(a.addr(), 5001).unwrap();
+    client.sendv(b"ipc", None, None, None, None, MsgFlags::empty()).unwrap();
+    let (_addr, _port, mut conn) = server.accept().unwrap();
+    let mut buf = [0u8; 16];
+    let (len, _from, _rcv_info, _flags) = conn.recvv(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"ipc");
+}
+
+#[test]
+fn unix_transport_bind() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    let dir = ::std::env::temp_dir();
+    let path_a = dir.join(format!("rusrsctp-{}-a.sock", ::std::process::id()));
+    let path_b = dir.join(format!("rusrsctp-{}-b.sock", ::std::process::id()));
+    let _ = ::std::fs::remove_file(&path_a);
+    let _ = ::std::fs::remove_file(&path_b);
+    {
+        // b is not bound yet as a is
+        let a = UnixTransport::bind(&sctp, &path_a, &path_b).unwrap();
+        let b = UnixTransport::bind(&sctp, &path_b, &path_a).unwrap();
+        let mut server = sctp.socket::<Conn, OneToOne>().unwrap();
+        server.bind(b.addr(), 5020).unwrap();
+        server.listen(1).unwrap();
+        let mut client = sctp.socket::<Conn, OneToOne>().unwrap();
+        client.bind(a.addr(), 5020).unwrap();
+        client.connect(a.addr(), 5020).unwrap();
+        client.sendv(b"paths", None, None, None, None, MsgFlags::empty()).unwrap();
+        let (_addr, _port, mut conn) = server.accept().unwrap();
+        let mut buf = [0u8; 16];
+        let (len, _from, _rcv_info, _flags) = conn.recvv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"paths");
+    }
+    ::std::fs::remove_file(&path_a).unwrap();
+    ::std::fs::remove_file(&path_b).unwrap();
+}
+
+#[test]
+fn framed_transport() {
+    use std::net::{TcpListener, TcpStream};
+    let sctp = UsrSctp::new(Some(9899), true);
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let tcp_a = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (tcp_b, _) = listener.accept().unwrap();
+    let a = FramedTransport::tcp(&sctp, tcp_a).unwrap();
+    let b = FramedTransport::tcp(&sctp, tcp_b).unwrap();
+    let mut server = sctp.socket::<Conn, OneToOne>().unwrap();
+    server.bind(b.addr(), 5021).unwrap();
+    server.listen(1).unwrap();
+    let mut client = sctp.socket::<Conn, OneToOne>().unwrap();
+    client.bind(a.addr(), 5021).unwrap();
+    client.connect(a.addr(), 5021).unwrap();
+    let snd_info = SndInfo { sid: 1, ..Default::default() };
+    client.sendv(b"tunnelled", None, Some(snd_info), None, None, MsgFlags::empty()).unwrap();
+    let (_addr, _port, mut conn) = server.accept().unwrap();
+    let mut buf = [0u8; 16];
+    let (len, _from, _rcv_info, _flags) = conn.recvv(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"tunnelled");
+}
+
+#[cfg(feature = "dtls")]
+#[test]
+fn dtls_transport() {
+    use std::net::UdpSocket;
+    let sctp = UsrSctp::new(Some(9899), true);
+    let udp_a = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let udp_b = UdpSocket::bind("127.0.0.1:0").unwrap();
+    udp_a.connect(udp_b.local_addr().unwrap()).unwrap();
+    udp_b.connect(udp_a.local_addr().unwrap()).unwrap();
+    let config_a = DtlsConfig::self_signed("a").unwrap();
+    let config_b = DtlsConfig::self_signed("b").unwrap();
+    let fingerprint_a = config_a.fingerprint().unwrap();
+    let config_a = config_a.verify_fingerprint(config_b.fingerprint().unwrap());
+    let config_b = config_b.verify_fingerprint(fingerprint_a);
+    let (listening_tx, listening) = ::std::sync::mpsc::channel();
+    ::std::thread::scope(|scope| {
+        let server = scope.spawn(|| {
+            let listening_tx = listening_tx;
+            let transport = DtlsTransport::accept(&sctp, udp_b, &config_b).unwrap();
+            let mut socket = sctp.socket::<Conn, OneToOne>().unwrap();
+            socket.bind(transport.addr(), 5000).unwrap();
+            socket.listen(1).unwrap();
+            listening_tx.send(()).unwrap();
+            let (_addr, _port, mut conn) = socket.accept().unwrap();
+            let mut buf = [0u8; 16];
+            let (len, _from, _rcv_info, _flags) = conn.recvv(&mut buf).unwrap();
+            buf[..len].to_vec()
+        });
+        let transport = DtlsTransport::connect(&sctp, udp_a, &config_a).unwrap();
+        assert_eq!(transport.role(), DtlsRole::Client);
+        let mut socket = sctp.socket::<Conn, OneToOne>().unwrap();
+        socket.bind(transport.addr(), 5000).unwrap();
+        // An INIT to a port nobody listens on is answered with an ABORT, so wait
+        // for the server to listen
+        listening.recv().unwrap();
+        socket.connect(transport.addr(), 5000).unwrap();
+        socket.sendv(b"secret", None, None, None, None, MsgFlags::empty()).unwrap();
+        assert_eq!(server.join().unwrap(), b"secret".to_vec());
+    });
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn async_connect_and_send() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    let mut server = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    server.bind(Ipv4Addr::new(127, 0, 0, 1), 5002).unwrap();
+    server.listen(8).unwrap();
+    ::std::thread::scope(|scope| {
+        let received = scope.spawn(move || {
+            let (_addr, _port, mut conn) = server.accept().unwrap();
+            let mut buf = [0u8; 16];
+            let (len, _from, _rcv_info, _flags) = conn.recvv(&mut buf).unwrap();
+            buf[..len].to_vec()
+        });
+        let runtime = ::tokio::runtime::Builder::new_current_thread()
+            .enable_io().build().unwrap();
+        let mut client = runtime.block_on(
+            AsyncSctpStream::<Ipv4>::connect(&sctp, Ipv4Addr::new(127, 0, 0, 1), 5002)).unwrap();
+        assert_eq!(runtime.block_on(client.send(b"hello", None)).unwrap(), 5);
+        assert_eq!(received.join().unwrap(), b"hello".to_vec());
+    });
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn async_framed_transport() {
+    use futures::future::{self, Either};
+    let sctp = UsrSctp::new(Some(9899), true);
+    let runtime = ::tokio::runtime::Builder::new_current_thread()
+        .enable_io().build().unwrap();
+    let (tcp_a, tcp_b) = {
+        let listener = ::std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let tcp_a = ::std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (tcp_b, _) = listener.accept().unwrap();
+        tcp_a.set_nonblocking(true).unwrap();
+        tcp_b.set_nonblocking(true).unwrap();
+        let _runtime = runtime.enter();
+        (::tokio::net::TcpStream::from_std(tcp_a).unwrap(),
+         ::tokio::net::TcpStream::from_std(tcp_b).unwrap())
+    };
+    let a = AsyncFramedTransport::new(&sctp);
+    let b = AsyncFramedTransport::new(&sctp);
+    let mut server = sctp.socket::<Conn, OneToOne>().unwrap();
+    server.bind(b.addr(), 5023).unwrap();
+    server.listen(1).unwrap();
+    let (stop, stopped) = ::futures::channel::oneshot::channel::<()>();
+    let (a, b, runtime) = (&a, &b, &runtime);
+    ::std::thread::scope(|scope| {
+        // The runtime carries the packets while SCTP is used (blocking) here
+        let carrier = scope.spawn(move || {
+            let links = future::select(a.run(tcp_a), b.run(tcp_b));
+            match runtime.block_on(future::select(links, stopped)) {
+                Either::Left(_) => panic!("a link ended"),
+                Either::Right(_) => (),
+            }
+        });
+        let mut client = sctp.socket::<Conn, OneToOne>().unwrap();
+        client.bind(a.addr(), 5023).unwrap();
+        client.connect(a.addr(), 5023).unwrap();
+        client.sendv(b"over tokio", None, None, None, None, MsgFlags::empty()).unwrap();
+        let (_addr, _port, mut conn) = server.accept().unwrap();
+        let mut buf = [0u8; 16];
+        let (len, _from, _rcv_info, _flags) = conn.recvv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"over tokio");
+        stop.send(()).unwrap();
+        carrier.join().unwrap();
+    });
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn async_connect_refused() {
+    let sctp = UsrSctp::new(Some(9899), true);
+    // nothing listens on this port, so the peer aborts the INIT
+    let connect = AsyncSctpStream::<Ipv4>::connect(&sctp, Ipv4Addr::new(127, 0, 0, 1), 5013);
+    let runtime = ::tokio::runtime::Builder::new_current_thread()
+        .enable_io().build().unwrap();
+    match runtime.block_on(connect) {
+        Ok(_) => panic!("connected with nobody listening"),
+        Err(e) => assert_eq!(e.0, ::libc::ECONNREFUSED),
+    }
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn message_stream() {
+    use futures::{SinkExt, StreamExt};
+
+    let sctp = UsrSctp::new(Some(9899), true);
+    let mut server = sctp.socket::<Ipv4, OneToOne>().unwrap();
+    server.bind(Ipv4Addr::new(127, 0, 0, 1), 5011).unwrap();
+    server.listen(8).unwrap();
+    ::std::thread::scope(|scope| {
+        scope.spawn(move || {
+            // echo one message back, on stream 3 with PPID 51
+            let (_addr, _port, mut conn) = server.accept().unwrap();
+            let mut buf = [0u8; 16];
+            let (len, _from, _rcv_info, _flags) = conn.recvv(&mut buf).unwrap();
+            let snd_info = SndInfo { sid: 3, ppid: htonl(51), ..Default::default() };
+            conn.sendv(&buf[..len], None, Some(snd_info), None, None, MsgFlags::empty()).unwrap();
+        });
+        let runtime = ::tokio::runtime::Builder::new_current_thread()
+            .enable_io().build().unwrap();
+        let client = runtime.block_on(
+            AsyncSctpStream::<Ipv4>::connect(&sctp, Ipv4Addr::new(127, 0, 0, 1), 5011)).unwrap();
+        let mut messages = client.into_messages().unwrap();
+        runtime.block_on(messages.send(OutgoingMessage::new(b"ping".to_vec(), 0, 0))).unwrap();
+        let message = runtime.block_on(messages.next()).unwrap().unwrap();
+        assert_eq!(message.data, b"ping".to_vec());
+        assert_eq!(message.sid, 3);
+        assert_eq!(ntohl(message.ppid), 51);
+    });
 }
@@ -9,6 +9,13 @@ pub enum Shutdown {
     RdWr
 }
 
+/// Whether `bindx` adds or removes addresses
+#[derive(Debug, Copy, Clone)]
+pub enum Bindx {
+    Add,
+    Remove
+}
+
 bitflags! {
     pub struct MsgFlags: u32 {
         const OOB = MSG_OOB;
@@ -32,6 +39,8 @@ bitflags! {
         const ZEROCOPY = MSG_ZEROCOPY;
         const FASTOPEN = MSG_FASTOPEN;
         const CMSG_CLOEXEC = MSG_CMSG_CLOEXEC;
+        /// On receive: the data returned is a notification, not a user message
+        const NOTIFICATION = MSG_NOTIFICATION;
     }
 }
 
@@ -85,9 +94,22 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Readiness of a socket, as reported by `Socket::events`
+    pub struct SocketEvents: i32 {
+        /// Data (or a connection, on a listening socket) can be received
+        const READ = SCTP_EVENT_READ as i32;
+        /// Data can be sent
+        const WRITE = SCTP_EVENT_WRITE as i32;
+        /// An error is pending
+        const ERROR = SCTP_EVENT_ERROR as i32;
+    }
+}
+
 pub type AssocId = sctp_assoc_t;
 
 // this is bitwise the same as sctp_sndinfo but with cleaner rust types
+#[derive(Debug, Copy, Clone)]
 pub struct SndInfo {
     /// Stream number
     pub sid: u16,
@@ -123,6 +145,7 @@ impl Default for SndInfo {
 }
 
 /// Partially Reliable Policy (RFC3758)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u16)]
 pub enum PrPolicy {
     /// None indicates reliable transmission
@@ -134,6 +157,7 @@ pub enum PrPolicy {
 }
 
 // this is bitwise the same as sctp_prinfo but with cleaner rust types
+#[derive(Debug, Copy, Clone)]
 pub struct PrInfo {
     pub policy: PrPolicy,
     pub value: u32
@@ -158,3 +182,94 @@ impl Default for PrInfo {
 }
 
 pub type AuthInfo = sctp_authinfo;
+
+/// Information about a received message (from sctp_rcvinfo)
+#[derive(Debug, Copy, Clone)]
+pub struct RcvInfo {
+    /// Stream number the message arrived on
+    pub sid: u16,
+    /// Stream sequence number.  If I-DATA (RFC 8260) is in use, this is only the
+    /// lower 16 bits of the message identifier (MID), which is all usrsctp reports.
+    pub ssn: u16,
+    /// Receive flags. UNORDERED is set for messages sent unordered.
+    pub flags: SctpFlags,
+    /// value passed by remote (SCTP stack does no byte order modification of this)
+    pub ppid: u32,
+    /// Transmission sequence number of the (first fragment of the) message
+    pub tsn: u32,
+    /// Current cumulative TSN of the association
+    pub cumtsn: u32,
+    /// Local context value (see SCTP_CONTEXT)
+    pub context: u32,
+    /// Association the message arrived on
+    pub assoc_id: AssocId,
+}
+
+impl RcvInfo {
+    #[inline]
+    pub fn from_sctp_rcvinfo(info: sctp_rcvinfo) -> RcvInfo {
+        RcvInfo {
+            sid: info.rcv_sid,
+            ssn: info.rcv_ssn,
+            flags: SctpFlags::from_bits_truncate(info.rcv_flags),
+            ppid: info.rcv_ppid,
+            tsn: info.rcv_tsn,
+            cumtsn: info.rcv_cumtsn,
+            context: info.rcv_context,
+            assoc_id: info.rcv_assoc_id,
+        }
+    }
+}
+
+/// A complete received message (or notification, if `flags` contains
+/// `MsgFlags::NOTIFICATION`)
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub data: Vec<u8>,
+    /// Stream the message arrived on (0 if `rcv_info` is not available)
+    pub sid: u16,
+    /// value passed by remote (0 if `rcv_info` is not available)
+    pub ppid: u32,
+    pub flags: MsgFlags,
+    /// Only available if the `RecvRcvInfo` option is on
+    pub rcv_info: Option<RcvInfo>,
+}
+
+/// A message to send, with the ancillary information `sendv` takes
+#[derive(Debug, Clone)]
+pub struct OutgoingMessage {
+    pub data: Vec<u8>,
+    pub snd_info: Option<SndInfo>,
+    pub pr_info: Option<PrInfo>,
+    pub auth_info: Option<AuthInfo>,
+}
+
+impl OutgoingMessage {
+    /// A message to send on stream `sid` with payload protocol identifier `ppid`
+    pub fn new(data: Vec<u8>, sid: u16, ppid: u32) -> OutgoingMessage {
+        OutgoingMessage {
+            data: data,
+            snd_info: Some(SndInfo {
+                sid: sid,
+                ppid: ppid,
+                ..Default::default()
+            }),
+            pr_info: None,
+            auth_info: None,
+        }
+    }
+}
+
+bitflags! {
+    /// What a stream reset event (`Notification::StreamReset`) reports
+    pub struct StreamResetFlags: u16 {
+        /// Streams the peer sends on were reset
+        const INCOMING = SCTP_STREAM_RESET_INCOMING_SSN as u16;
+        /// Streams we send on were reset
+        const OUTGOING = SCTP_STREAM_RESET_OUTGOING_SSN as u16;
+        /// The peer refused the reset
+        const DENIED = SCTP_STREAM_RESET_DENIED as u16;
+        /// The reset failed
+        const FAILED = SCTP_STREAM_RESET_FAILED as u16;
+    }
+}
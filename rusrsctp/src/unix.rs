@@ -0,0 +1,105 @@
+
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use errno::Errno;
+use libc;
+use super::{UsrSctp, ConnAddr, conn_input};
+
+// How often the reader checks whether it should stop
+const READ_TIMEOUT: Duration = Duration::from_millis(50);
+// Larger than any packet SCTP sends
+const MAX_DATAGRAM: usize = 65536;
+
+fn io_to_errno(e: io::Error) -> Errno {
+    Errno(e.raw_os_error().unwrap_or(libc::EIO))
+}
+
+/// SCTP between processes on the same host, over a Unix domain datagram socket:
+/// an AF_CONN link with no UDP port and no raw IP (so no root) needed.
+///
+/// Create sockets of type `Socket<Conn>`, and bind and connect them to `addr()`,
+/// as with an `Endpoint`.  A thread of our own receives from the Unix socket.
+///
+/// For an abstract socket (on Linux), bind and connect the `UnixDatagram` with
+/// `std::os::linux::net::SocketAddrExt::from_abstract_name` and pass it to `new`.
+pub struct UnixTransport<'a> {
+    sctp: &'a UsrSctp,
+    addr: ConnAddr,
+    stop: Arc<AtomicBool>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl<'a> UnixTransport<'a> {
+    /// Bind to the filesystem socket `local` and send to the one at `peer`.  The
+    /// peer need not be bound yet: until it is, packets are lost, and SCTP sends
+    /// them again.
+    pub fn bind<P, Q>(sctp: &'a UsrSctp, local: P, peer: Q) -> Result<UnixTransport<'a>, Errno>
+        where P: AsRef<Path>, Q: AsRef<Path>
+    {
+        let socket = UnixDatagram::bind(local).map_err(io_to_errno)?;
+        UnixTransport::start(sctp, socket, Some(peer.as_ref().to_path_buf()))
+    }
+
+    /// Use a socket which is already connected to the peer (e.g. one of a
+    /// `UnixDatagram::pair`)
+    pub fn new(sctp: &'a UsrSctp, socket: UnixDatagram) -> Result<UnixTransport<'a>, Errno> {
+        UnixTransport::start(sctp, socket, None)
+    }
+
+    // Sends go to `peer`, or to the socket's own peer for none
+    fn start(sctp: &'a UsrSctp, socket: UnixDatagram, peer: Option<PathBuf>)
+             -> Result<UnixTransport<'a>, Errno>
+    {
+        socket.set_read_timeout(Some(READ_TIMEOUT)).map_err(io_to_errno)?;
+        let writer = socket.try_clone().map_err(io_to_errno)?;
+        let addr = sctp.register_conn(move |packet| {
+            // lost packets are SCTP's to recover
+            let _ = match peer {
+                Some(ref peer) => writer.send_to(packet, peer),
+                None => writer.send(packet),
+            };
+        });
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let reader = thread::spawn(move || {
+            let mut buf = vec![0u8; MAX_DATAGRAM];
+            while !stopped.load(Ordering::SeqCst) {
+                match socket.recv(&mut buf) {
+                    Ok(len) if len > 0 => conn_input(addr, &buf[..len]),
+                    Ok(_) => (),
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut
+                        || e.kind() == io::ErrorKind::Interrupted => (),
+                    // The socket is of no more use; SCTP sees the link as lost
+                    Err(_) => break,
+                }
+            }
+        });
+        Ok(UnixTransport {
+            sctp: sctp,
+            addr: addr,
+            stop: stop,
+            reader: Some(reader),
+        })
+    }
+
+    /// The address of this link, to bind and connect sockets to
+    pub fn addr(&self) -> ConnAddr {
+        self.addr
+    }
+}
+
+impl<'a> Drop for UnixTransport<'a> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+        self.sctp.deregister_conn(self.addr);
+    }
+}
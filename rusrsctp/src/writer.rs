@@ -0,0 +1,94 @@
+
+use std::io::{self, Write};
+use errno::Errno;
+use super::{Socket, Ip, Mode, SndInfo, SctpFlags, MsgFlags, ExplicitEor};
+
+/// Writes one message in pieces, for messages too large to send (or to hold in
+/// memory) at once.  Get one from `Socket::begin_message`.
+///
+/// Each `write` is sent straight away as a fragment of the message, and
+/// `finish` ends it.  A message that was started but never finished cannot be
+/// taken back, and would hold up its stream for good, so dropping the writer
+/// unfinished aborts the association.
+pub struct MessageWriter<'s, 'a: 's, T: 'a + Ip, M: 's + Mode> {
+    socket: &'s mut Socket<'a, T, M>,
+    snd_info: SndInfo,
+    started: bool,
+    finished: bool,
+}
+
+impl<'a, T: 'a + Ip, M: Mode> Socket<'a, T, M> {
+    /// Start sending a message on the stream (and with the PPID, etc.) of
+    /// `snd_info`, and on one-to-many sockets, to its association.  `snd_info` is
+    /// taken as `sendv` takes it.  This turns on `ExplicitEor` until the message
+    /// is finished.
+    pub fn begin_message<'s>(&'s mut self, snd_info: SndInfo)
+                             -> Result<MessageWriter<'s, 'a, T, M>, Errno>
+    {
+        self.setsockopt(ExplicitEor(1))?;
+        let mut snd_info = M::filter_snd_info(snd_info);
+        snd_info.flags.remove(SctpFlags::EOR);
+        Ok(MessageWriter {
+            socket: self,
+            snd_info: snd_info,
+            started: false,
+            finished: false,
+        })
+    }
+}
+
+impl<'s, 'a: 's, T: 'a + Ip, M: 's + Mode> MessageWriter<'s, 'a, T, M> {
+    /// End the message.  If that fails, the message is given up on as when the
+    /// writer is dropped unfinished.
+    pub fn finish(mut self) -> Result<(), Errno> {
+        let mut snd_info = self.snd_info;
+        snd_info.flags.insert(SctpFlags::EOR);
+        self.socket.sendv_inner(&[], None, Some(snd_info), None, None, MsgFlags::empty())?;
+        self.finished = true;
+        self.socket.setsockopt(ExplicitEor(0))
+    }
+
+    /// Give up on the message, aborting the association if any of it was sent
+    pub fn abort(mut self) -> Result<(), Errno> {
+        self.finished = true;
+        self.abort_inner()
+    }
+
+    // ExplicitEor is turned off again even if the abort could not be sent
+    fn abort_inner(&mut self) -> Result<(), Errno> {
+        let aborted = if self.started {
+            let mut snd_info = self.snd_info;
+            snd_info.flags.insert(SctpFlags::ABORT);
+            self.socket.sendv_inner(&[], None, Some(snd_info), None, None, MsgFlags::empty())
+                .map(|_| ())
+        } else {
+            Ok(())
+        };
+        let restored = self.socket.setsockopt(ExplicitEor(0));
+        aborted.and(restored)
+    }
+}
+
+impl<'s, 'a: 's, T: 'a + Ip, M: 's + Mode> Write for MessageWriter<'s, 'a, T, M> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let len = self.socket.sendv_inner(buf, None, Some(self.snd_info), None, None, MsgFlags::empty())
+            .map_err(|e| io::Error::from_raw_os_error(e.0))?;
+        self.started = true;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(()) // every write is sent straight away
+    }
+}
+
+impl<'s, 'a: 's, T: 'a + Ip, M: 's + Mode> Drop for MessageWriter<'s, 'a, T, M> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.abort_inner();
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// Endpoint with SCTP's clock driven by the test, in a binary of its own as SCTP
+// without threads is process wide (and the timers of other tests would run
+// when this one advances the clock)
+
+extern crate rusrsctp;
+
+use std::time::Duration;
+use rusrsctp::*;
+
+#[test]
+fn endpoint() {
+    let sctp = UsrSctp::new_nothreads(None, false);
+    let mut a = Endpoint::new(&sctp);
+    let mut b = Endpoint::new(&sctp);
+    assert!(a.addr() != b.addr());
+    // SCTP's clock starts at the first handle_timeout
+    let mut now = a.poll_timeout().unwrap();
+    a.handle_timeout(now);
+
+    let mut server = b.socket::<OneToOne>().unwrap();
+    server.bind(b.addr(), 5000).unwrap();
+    server.listen(8).unwrap();
+    server.set_non_blocking(true).unwrap();
+    let mut client = a.socket::<OneToOne>().unwrap();
+    client.bind(a.addr(), 5000).unwrap();
+    client.set_non_blocking(true).unwrap();
+    let _ = client.connect(a.addr(), 5000); // EINPROGRESS
+
+    // Lose the INIT, and wake up when told to until it is sent again
+    assert!(a.poll_transmit().is_some());
+    let start = now;
+    let init = loop {
+        let deadline = a.poll_timeout().expect("the INIT timer is running");
+        assert!(deadline >= now);
+        assert!(deadline - start < Duration::from_secs(60),
+                "INIT was never retransmitted");
+        now = deadline;
+        a.handle_timeout(now);
+        if let Some(datagram) = a.poll_transmit() {
+            break datagram;
+        }
+    };
+    assert!(now > start);
+    b.handle_input(&init);
+
+    // Carry the datagrams between the endpoints until the association is up
+    loop {
+        let mut carried = 0;
+        while let Some(datagram) = a.poll_transmit() {
+            b.handle_input(&datagram);
+            carried += 1;
+        }
+        while let Some(datagram) = b.poll_transmit() {
+            a.handle_input(&datagram);
+            carried += 1;
+        }
+        if carried == 0 {
+            break;
+        }
+    }
+    let (addr, port, _socket) = server.accept().unwrap();
+    assert_eq!(addr, b.addr());
+    assert!(port != 0);
+}
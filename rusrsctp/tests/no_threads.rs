@@ -0,0 +1,79 @@
+// SCTP without threads is process wide, so these tests need a binary of their
+// own, apart from the threaded tests in src/tests.rs
+
+extern crate libc;
+extern crate rusrsctp;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use rusrsctp::*;
+
+type Queue = Arc<Mutex<VecDeque<Vec<u8>>>>;
+
+// A link whose packets are queued for the test to deliver (or lose)
+fn link(sctp: &UsrSctp) -> (ConnAddr, Queue) {
+    let queue: Queue = Arc::new(Mutex::new(VecDeque::new()));
+    let out = queue.clone();
+    let addr = sctp.register_conn(move |packet| out.lock().unwrap().push_back(packet.to_vec()));
+    (addr, queue)
+}
+
+fn deliver(sctp: &UsrSctp, from: &Queue, to: ConnAddr) -> usize {
+    let mut count = 0;
+    loop {
+        // do not hold the lock while SCTP handles the packet, as it may answer
+        let packet = from.lock().unwrap().pop_front();
+        match packet {
+            Some(packet) => sctp.conn_input(to, &packet),
+            None => return count,
+        }
+        count += 1;
+    }
+}
+
+#[test]
+fn handshake_driven_by_advance() {
+    let sctp = UsrSctp::new_nothreads(None, false);
+    let (a, from_a) = link(&sctp);
+    let (b, from_b) = link(&sctp);
+
+    let mut server = sctp.socket::<Conn, OneToOne>().unwrap();
+    server.bind(b, 5000).unwrap();
+    server.listen(8).unwrap();
+    server.set_non_blocking(true).unwrap();
+    let mut client = sctp.socket::<Conn, OneToOne>().unwrap();
+    client.bind(a, 5000).unwrap();
+    client.set_non_blocking(true).unwrap();
+    assert_eq!(client.poll_connect().unwrap_err().0, libc::ENOTCONN);
+    // Blocking until connected would wait on a clock that only we move
+    let mut blocking = sctp.socket::<Conn, OneToOne>().unwrap();
+    let err = blocking.connect_timeout(b, 5000, Duration::from_secs(1), None).unwrap_err();
+    assert_eq!(err.0, libc::EINVAL);
+    let _ = client.connect(a, 5000); // EINPROGRESS
+
+    // Lose the INIT.  Nothing more is sent until the clock moves on.
+    assert_eq!(from_a.lock().unwrap().drain(..).count(), 1);
+    sctp.advance(Duration::from_millis(0));
+    assert!(from_a.lock().unwrap().is_empty());
+
+    // The retransmission timer sends it again
+    let mut waited = Duration::from_millis(0);
+    while from_a.lock().unwrap().is_empty() {
+        assert!(waited < Duration::from_secs(60), "INIT was never retransmitted");
+        sctp.advance(Duration::from_millis(100));
+        waited += Duration::from_millis(100);
+    }
+    assert!(waited >= Duration::from_millis(100));
+
+    // and this time it gets through
+    while deliver(&sctp, &from_a, b) + deliver(&sctp, &from_b, a) > 0 {}
+    assert!(client.poll_connect().unwrap());
+    let (_addr, _port, mut conn) = server.accept().unwrap();
+
+    client.sendv(b"no threads", None, None, None, None, MsgFlags::empty()).unwrap();
+    while deliver(&sctp, &from_a, b) + deliver(&sctp, &from_b, a) > 0 {}
+    let mut buf = [0u8; 16];
+    let (len, _from, _rcv_info, _flags) = conn.recvv(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"no threads");
+}